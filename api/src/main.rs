@@ -7,11 +7,11 @@ use axum::{
     routing::get,
 };
 
-use tokio::net::TcpListener;
+use tokio::{net::TcpListener, time::timeout};
 use tower_http::cors::CorsLayer;
-use tracing::info;
+use tracing::{info, warn};
 
-use shared::tracing_subscriber;
+use shared::{shutdown_deadline, shutdown_signal, tracing_subscriber};
 
 mod endpoints {
     pub(crate) mod health;
@@ -23,6 +23,7 @@ async fn main() -> Result<(), Error> {
     tracing_subscriber();
 
     let addr = env::var("ADDRESS").unwrap_or_else(|_| "0.0.0.0:80".to_string());
+    let deadline = shutdown_deadline()?;
 
     let app = Router::new()
         .route("/api/schedule", get(endpoints::schedule::get))
@@ -31,7 +32,24 @@ async fn main() -> Result<(), Error> {
 
     info!(addr, "starting api http server");
 
-    axum::serve(TcpListener::bind(addr).await?, app).await?;
+    let server = axum::serve(TcpListener::bind(addr).await?, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .into_future();
+
+    tokio::pin!(server);
+
+    // requests in flight get until the deadline once the signal arrived
+    tokio::select! {
+        result = &mut server => return Ok(result?),
+        _ = shutdown_signal() => {}
+    }
+
+    info!(?deadline, "shutting down, finishing requests");
+
+    match timeout(deadline, server).await {
+        Ok(result) => result?,
+        Err(_) => warn!("shutdown deadline passed, exiting anyway"),
+    }
 
     Ok(())
 }
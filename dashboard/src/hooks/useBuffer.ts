@@ -84,6 +84,10 @@ export const useBuffer = <T>() => {
 		}
 	};
 
+	const clear = () => {
+		bufferRef.current = [];
+	};
+
 	const maxDelay = (): number => {
 		return bufferRef.current.length > 0 ? Math.floor((Date.now() - bufferRef.current[0].timestamp) / 1000) : 0;
 	};
@@ -94,6 +98,7 @@ export const useBuffer = <T>() => {
 		latest,
 		delayed,
 		cleanup,
+		clear,
 		maxDelay,
 	};
 };
@@ -17,11 +17,12 @@ const UPDATE_MS = 200;
 
 type Props = {
 	updateState: (state: State) => void;
+	replaceState: (state: State | null) => void;
 	updatePosition: (pos: Positions) => void;
 	updateCarData: (car: CarsData) => void;
 };
 
-export const useDataEngine = ({ updateState, updatePosition, updateCarData }: Props) => {
+export const useDataEngine = ({ updateState, replaceState, updatePosition, updateCarData }: Props) => {
 	const buffers = {
 		ExtrapolatedClock: useStatefulBuffer(),
 		TopThree: useStatefulBuffer(),
@@ -83,6 +84,16 @@ export const useDataEngine = ({ updateState, updatePosition, updateCarData }: Pr
 		}
 	};
 
+	// a new session started, nothing of the previous one may stay around
+	const handleSession = (session: MessageInitial) => {
+		Object.values(buffers).forEach((buffer) => buffer.clear());
+		carBuffer.clear();
+		posBuffer.clear();
+
+		replaceState(null);
+		handleInitial(session);
+	};
+
 	const handleUpdate = ({ CarDataZ: carZ, PositionZ: posZ, ...update }: MessageUpdate) => {
 		Object.keys(buffers).forEach((key) => {
 			const data = update[key as keyof typeof update];
@@ -173,6 +184,7 @@ export const useDataEngine = ({ updateState, updatePosition, updateCarData }: Pr
 	return {
 		handleUpdate,
 		handleInitial,
+		handleSession,
 		maxDelay,
 	};
 };
@@ -6,10 +6,11 @@ import { env } from "@/env";
 
 type Props = {
 	handleInitial: (data: MessageInitial) => void;
+	handleSession: (data: MessageInitial) => void;
 	handleUpdate: (data: MessageUpdate) => void;
 };
 
-export const useSocket = ({ handleInitial, handleUpdate }: Props) => {
+export const useSocket = ({ handleInitial, handleSession, handleUpdate }: Props) => {
 	const [connected, setConnected] = useState<boolean>(false);
 
 	useEffect(() => {
@@ -22,6 +23,11 @@ export const useSocket = ({ handleInitial, handleUpdate }: Props) => {
 			handleInitial(JSON.parse(message.data));
 		});
 
+		// a new session replaces the state of the previous one
+		sse.addEventListener("session", (message) => {
+			handleSession(JSON.parse(message.data));
+		});
+
 		sse.addEventListener("update", (message) => {
 			handleUpdate(JSON.parse(message.data));
 		});
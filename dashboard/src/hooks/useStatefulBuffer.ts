@@ -15,8 +15,14 @@ export const useStatefulBuffer = <T>() => {
 		if (currentRef.current) buffer.push(currentRef.current);
 	};
 
+	const clear = () => {
+		currentRef.current = null;
+		buffer.clear();
+	};
+
 	return {
 		push,
+		clear,
 		latest: buffer.latest,
 		delayed: buffer.delayed,
 		cleanup: buffer.cleanup,
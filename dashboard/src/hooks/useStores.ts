@@ -4,6 +4,7 @@ import { useDataStore } from "@/stores/useDataStore";
 
 type Fns = {
 	updateState: (state: State) => void;
+	replaceState: (state: State | null) => void;
 	updatePosition: (pos: Positions) => void;
 	updateCarData: (car: CarsData) => void;
 };
@@ -13,6 +14,7 @@ export const useStores = (): Fns => {
 
 	return {
 		updateState: (v) => dataStore.setState(v),
+		replaceState: (v) => dataStore.replaceState(v),
 		updatePosition: (v) => dataStore.setPositions(v),
 		updateCarData: (v) => dataStore.setCarsData(v),
 	};
@@ -10,6 +10,8 @@ type DataStore = {
 	positions: Positions | null;
 
 	setState: (state: Partial<State> | null) => void;
+	// replaces everything, unlike setState which merges the topics into the current state
+	replaceState: (state: State | null) => void;
 	setCarsData: (carsData: CarsData | null) => void;
 	setPositions: (positions: Positions | null) => void;
 };
@@ -29,6 +31,7 @@ export const useDataStore = create<DataStore>((set) => ({
 					}
 				: null,
 		})),
+	replaceState: (state: State | null) => set({ state, carsData: null, positions: null }),
 	setCarsData: (carsData: CarsData | null) => set({ carsData }),
 	setPositions: (positions: Positions | null) => set({ positions }),
 }));
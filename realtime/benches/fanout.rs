@@ -0,0 +1,163 @@
+//! Compares copying updates and serializing the state per client against the shared
+//! buffers and cached snapshot realtime uses. Run with `cargo bench -p realtime`.
+//!
+//! Sharing an update only saves the copy into each receiver's channel, `Event::data`
+//! still copies it into the event every client encodes. The second comparison includes
+//! that encoding, to show what's left of the saving on the way to the socket.
+
+use std::{
+    convert::Infallible,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::response::{
+    IntoResponse,
+    sse::{Event, Sse},
+};
+use serde_json::{Value, json};
+use tokio::sync::{RwLock, broadcast};
+
+use realtime::services::state_service::StateService;
+
+const RECEIVERS: usize = 500;
+const UPDATES: usize = 200;
+const CONNECTS: usize = 500;
+
+#[tokio::main]
+async fn main() {
+    let update = json!({ "CarData.z": "x".repeat(4 * 1024) }).to_string();
+
+    let copied = fanout(update.clone()).await;
+    let shared = fanout::<Arc<str>>(update.clone().into()).await;
+
+    println!("fan-out of {UPDATES} updates to {RECEIVERS} receivers");
+    report(copied, shared);
+
+    let copied = fanout_encoded(update.clone()).await;
+    let shared = fanout_encoded::<Arc<str>>(update.clone().into()).await;
+
+    println!("the same fan-out, encoded as SSE events");
+    report(copied, shared);
+
+    let state = large_state();
+
+    let serialized = connect_storm_serializing(state.clone()).await;
+    let cached = connect_storm_cached(state).await;
+
+    println!("{CONNECTS} clients fetching the initial state");
+    report(serialized, cached);
+}
+
+fn report(before: Duration, after: Duration) {
+    println!("  per client copy:  {before:?}");
+    println!("  shared:           {after:?}");
+    println!(
+        "  speedup:          {:.1}x",
+        before.as_secs_f64() / after.as_secs_f64()
+    );
+}
+
+async fn fanout<T: Clone + Send + 'static>(update: T) -> Duration {
+    let (tx, _) = broadcast::channel::<T>(UPDATES);
+    let receivers = (0..RECEIVERS).map(|_| tx.subscribe()).collect::<Vec<_>>();
+
+    let start = Instant::now();
+
+    for _ in 0..UPDATES {
+        let _ = tx.send(update.clone());
+    }
+
+    for mut rx in receivers {
+        while let Ok(update) = rx.try_recv() {
+            std::hint::black_box(update);
+        }
+    }
+
+    start.elapsed()
+}
+
+/// Like [`fanout`], with every receiver encoding its updates into an SSE body,
+/// the way `/api/realtime` sends them.
+async fn fanout_encoded<T>(update: T) -> Duration
+where
+    T: AsRef<str> + Clone + Send + 'static,
+{
+    let (tx, _) = broadcast::channel::<T>(UPDATES);
+    let receivers = (0..RECEIVERS).map(|_| tx.subscribe()).collect::<Vec<_>>();
+
+    let start = Instant::now();
+
+    for _ in 0..UPDATES {
+        let _ = tx.send(update.clone());
+    }
+
+    for mut rx in receivers {
+        let mut events = Vec::with_capacity(UPDATES);
+
+        while let Ok(update) = rx.try_recv() {
+            let event = Event::default()
+                .event("update")
+                .id(events.len().to_string())
+                .data(update.as_ref());
+            events.push(Ok::<_, Infallible>(event));
+        }
+
+        let body = Sse::new(futures::stream::iter(events))
+            .into_response()
+            .into_body();
+
+        std::hint::black_box(axum::body::to_bytes(body, usize::MAX).await.unwrap());
+    }
+
+    start.elapsed()
+}
+
+/// Roughly the size of the state during a race.
+fn large_state() -> Value {
+    let drivers = (1..=20)
+        .map(|n| {
+            let laps = (0..60)
+                .map(|lap| json!({ "Lap": lap, "Time": "1:32.456", "Sectors": [31.2, 29.8, 31.4] }))
+                .collect::<Vec<_>>();
+            (n.to_string(), json!({ "Position": n, "Laps": laps }))
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    json!({ "TimingData": { "Lines": drivers }, "CarData.z": "x".repeat(64 * 1024) })
+}
+
+/// What every connection did before, serialize the state under the read lock.
+async fn connect_storm_serializing(state: Value) -> Duration {
+    let state = Arc::new(RwLock::new(state));
+    let start = Instant::now();
+
+    let tasks = (0..CONNECTS).map(|_| {
+        let state = state.clone();
+        tokio::spawn(async move { state.read().await.to_string() })
+    });
+
+    for task in tasks {
+        std::hint::black_box(task.await.unwrap());
+    }
+
+    start.elapsed()
+}
+
+async fn connect_storm_cached(state: Value) -> Duration {
+    let state_service = StateService::new();
+    state_service.set_state(state, 1).await.unwrap();
+
+    let start = Instant::now();
+
+    let tasks = (0..CONNECTS).map(|_| {
+        let state_service = state_service.clone();
+        tokio::spawn(async move { state_service.get_state_string().await.unwrap() })
+    });
+
+    for task in tasks {
+        std::hint::black_box(task.await.unwrap());
+    }
+
+    start.elapsed()
+}
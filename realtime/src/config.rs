@@ -0,0 +1,320 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Context, Error, anyhow};
+use axum::http::HeaderValue;
+use serde::Deserialize;
+use shared::config::{UpstreamConfig, env_list, env_override, env_override_some, load_config};
+use signalr::ReconnectPolicy;
+
+/// Settings from the TOML file at `CONFIG`, overridden by env vars.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// `ADDRESS`
+    pub address: String,
+    /// CORS origins, `ORIGIN` separated by `;`.
+    pub origins: Vec<String>,
+    /// Updates a client can fall behind before it's resynced, `BROADCAST_CAPACITY`.
+    pub broadcast_capacity: usize,
+    pub upstream: UpstreamConfig,
+    pub keep_alive: KeepAliveConfig,
+    pub restart: RestartConfig,
+    pub archive: ArchiveConfig,
+    pub replay: ReplayConfig,
+    pub relay: RelayConfig,
+    pub shutdown: ShutdownConfig,
+}
+
+/// Comments sent on idle SSE streams, so proxies don't close them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeepAliveConfig {
+    /// `KEEP_ALIVE_TEXT`
+    pub text: String,
+    /// Seconds, `KEEP_ALIVE_INTERVAL`.
+    pub interval: u64,
+}
+
+/// How the ingest loop restarts after it returned, backing off while it keeps failing.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RestartConfig {
+    /// Seconds before the first restart, `RESTART_DELAY`.
+    pub delay: u64,
+    /// Seconds the delay grows to at most, `RESTART_MAX_DELAY`.
+    pub max_delay: u64,
+    /// Failed restarts in a row after which realtime exits, `RESTART_MAX_ATTEMPTS`.
+    pub max_attempts: Option<u32>,
+}
+
+/// Sessions kept on disk, their recordings and final states.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveConfig {
+    /// Directory of the archived sessions, nothing is archived without it, `ARCHIVE_DIR`.
+    pub dir: Option<PathBuf>,
+    /// Seconds between syncing the recording to disk, `ARCHIVE_SYNC_INTERVAL`.
+    pub sync_interval: u64,
+}
+
+/// A recording made with `simulator save`, replayed instead of connecting to f1.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplayConfig {
+    /// `F1_REPLAY_FILE`
+    pub file: Option<PathBuf>,
+    /// Times the recorded pace, `F1_REPLAY_SPEED`.
+    pub speed: f64,
+}
+
+/// Another realtime instance whose SSE stream is relayed instead of connecting to f1.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelayConfig {
+    /// Its `/api/realtime` stream, `RELAY_URL`.
+    pub url: Option<String>,
+    /// Seconds without even a keep-alive before reconnecting, `RELAY_IDLE_TIMEOUT`.
+    pub idle_timeout: u64,
+}
+
+/// How the server winds down on SIGTERM / SIGINT.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// Seconds to drain clients before exiting anyway, `SHUTDOWN_DEADLINE`.
+    pub deadline: u64,
+    /// Milliseconds clients wait before reconnecting, `RECONNECT_HINT_MS`.
+    pub reconnect_hint: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            sync_interval: 10,
+        }
+    }
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            speed: 1.0,
+        }
+    }
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            // keep-alives are sent every 15 seconds
+            idle_timeout: 60,
+        }
+    }
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            deadline: 20,
+            reconnect_hint: 2000,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: "0.0.0.0:80".to_string(),
+            origins: vec!["https://f1-dash.com".to_string()],
+            broadcast_capacity: 16,
+            upstream: UpstreamConfig::default(),
+            keep_alive: KeepAliveConfig::default(),
+            restart: RestartConfig::default(),
+            archive: ArchiveConfig::default(),
+            replay: ReplayConfig::default(),
+            relay: RelayConfig::default(),
+            shutdown: ShutdownConfig::default(),
+        }
+    }
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            text: "keep-alive-text".to_string(),
+            interval: 15,
+        }
+    }
+}
+
+impl Default for RestartConfig {
+    fn default() -> Self {
+        Self {
+            delay: 2,
+            max_delay: 60,
+            max_attempts: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load and validate the config, errors name the setting that's wrong.
+    pub fn load() -> Result<Self, Error> {
+        let mut config: Config = load_config()?;
+
+        config.env_overrides()?;
+        config.validate().context("invalid config")?;
+
+        Ok(config)
+    }
+
+    fn env_overrides(&mut self) -> Result<(), Error> {
+        env_override("ADDRESS", &mut self.address)?;
+        env_override("BROADCAST_CAPACITY", &mut self.broadcast_capacity)?;
+
+        if let Some(origins) = env_list("ORIGIN", ';') {
+            self.origins = origins;
+        }
+
+        self.upstream.env_overrides()?;
+
+        env_override("KEEP_ALIVE_TEXT", &mut self.keep_alive.text)?;
+        env_override("KEEP_ALIVE_INTERVAL", &mut self.keep_alive.interval)?;
+
+        env_override("RESTART_DELAY", &mut self.restart.delay)?;
+        env_override("RESTART_MAX_DELAY", &mut self.restart.max_delay)?;
+
+        env_override_some("RESTART_MAX_ATTEMPTS", &mut self.restart.max_attempts)?;
+
+        if let Ok(dir) = std::env::var("ARCHIVE_DIR") {
+            self.archive.dir = Some(PathBuf::from(dir));
+        }
+
+        env_override("ARCHIVE_SYNC_INTERVAL", &mut self.archive.sync_interval)?;
+
+        if let Ok(file) = std::env::var("F1_REPLAY_FILE") {
+            self.replay.file = Some(PathBuf::from(file));
+        }
+
+        env_override("F1_REPLAY_SPEED", &mut self.replay.speed)?;
+
+        if let Ok(url) = std::env::var("RELAY_URL") {
+            self.relay.url = Some(url);
+        }
+
+        env_override("RELAY_IDLE_TIMEOUT", &mut self.relay.idle_timeout)?;
+
+        env_override("SHUTDOWN_DEADLINE", &mut self.shutdown.deadline)?;
+        env_override("RECONNECT_HINT_MS", &mut self.shutdown.reconnect_hint)?;
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        match self.address.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+            _ => return Err(anyhow!("address {:?} must be host:port", self.address)),
+        }
+
+        if self.origins.is_empty() {
+            return Err(anyhow!("origins must not be empty"));
+        }
+
+        for origin in &self.origins {
+            HeaderValue::from_str(origin)
+                .map_err(|_| anyhow!("origin {origin:?} is not a valid header value"))?;
+        }
+
+        if self.broadcast_capacity == 0 {
+            return Err(anyhow!("broadcast_capacity must be at least 1"));
+        }
+
+        self.upstream.validate()?;
+
+        if self.keep_alive.interval == 0 {
+            return Err(anyhow!("keep_alive.interval must be at least 1 second"));
+        }
+
+        if self.restart.max_delay < self.restart.delay {
+            return Err(anyhow!(
+                "restart.max_delay must not be shorter than restart.delay"
+            ));
+        }
+
+        if self.archive.sync_interval == 0 {
+            return Err(anyhow!("archive.sync_interval must be at least 1 second"));
+        }
+
+        if let Some(file) = &self.replay.file
+            && !file.is_file()
+        {
+            return Err(anyhow!("replay.file {} is not a file", file.display()));
+        }
+
+        if !self.replay.speed.is_finite() || self.replay.speed <= 0.0 {
+            return Err(anyhow!(
+                "replay.speed must be a positive number, got {}",
+                self.replay.speed
+            ));
+        }
+
+        if let Some(url) = &self.relay.url
+            && !url.starts_with("http://")
+            && !url.starts_with("https://")
+        {
+            return Err(anyhow!("relay.url {url:?} must be an http(s) url"));
+        }
+
+        if self.relay.idle_timeout == 0 {
+            return Err(anyhow!("relay.idle_timeout must be at least 1 second"));
+        }
+
+        if self.replay.file.is_some() && self.relay.url.is_some() {
+            return Err(anyhow!("set either replay.file or relay.url, not both"));
+        }
+
+        Ok(())
+    }
+}
+
+impl KeepAliveConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+}
+
+impl RestartConfig {
+    pub fn policy(&self) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(self.delay),
+            max_delay: Duration::from_secs(self.max_delay),
+            multiplier: 2.0,
+            max_attempts: self.max_attempts,
+        }
+    }
+}
+
+impl ArchiveConfig {
+    pub fn sync_interval(&self) -> Duration {
+        Duration::from_secs(self.sync_interval)
+    }
+}
+
+impl RelayConfig {
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout)
+    }
+}
+
+impl ShutdownConfig {
+    pub fn deadline(&self) -> Duration {
+        Duration::from_secs(self.deadline)
+    }
+
+    pub fn reconnect_hint(&self) -> Duration {
+        Duration::from_millis(self.reconnect_hint)
+    }
+}
@@ -1,55 +1,222 @@
+use std::{sync::Arc, time::Instant};
+
 use anyhow::Error;
-use serde_json::{Value, json};
+use chrono::SecondsFormat;
+use serde_json::{Map, Value, json};
+use shared::config::UpstreamConfig;
+use signalr::{ClientBuilder, Event, ReconnectPolicy, Resubscriber, UpdateArgs, parse_updates};
 use tokio::sync::broadcast::Sender;
-use tokio_stream::StreamExt;
-use tracing::{error, trace, warn};
-
-use crate::services::state_service::StateService;
-
-const URL: &str = "livetiming.formula1.com/signalr";
-const HUB: &str = "Streaming";
-
-const TOPICS: [&str; 17] = [
-    "Heartbeat",
-    "CarData.z",
-    "Position.z",
-    "ExtrapolatedClock",
-    "TimingStats",
-    "TimingAppData",
-    "WeatherData",
-    "TrackStatus",
-    "SessionStatus",
-    "DriverList",
-    "RaceControlMessages",
-    "SessionInfo",
-    "SessionData",
-    "LapCount",
-    "TimingData",
-    "TeamRadio",
-    "ChampionshipPrediction",
-];
+use tokio_stream::{Stream, StreamExt};
+use tracing::{error, info, trace, warn};
+
+use crate::services::{
+    archive::Archive,
+    journal::{Journal, Update, UpdateKind},
+    metrics,
+    session::Session,
+    shutdown::Listener,
+    state_service::{StateService, merge},
+    upstream::Upstream,
+};
+
+/// Key of the feed timestamp that is sent along with every update.
+pub const TIMESTAMP_KEY: &str = "_timestamp";
 
 pub async fn ingest_f1(
+    config: &UpstreamConfig,
     state_service: StateService,
-    update_sender: Sender<String>,
+    update_sender: Sender<Update>,
+    journal: Journal,
+    upstream: Upstream,
+    archive: Archive,
+    shutdown: &mut Listener,
+) -> Result<(), Error> {
+    let mut builder = ClientBuilder::new(config.url(), &config.hub)
+        .protocol(config.protocol.parse()?)
+        .endpoint(config.dev_url.clone());
+
+    if let Some(timeout) = config.idle_timeout() {
+        builder = builder.idle_timeout(timeout);
+    }
+
+    let client = builder.reconnecting(&config.topics(), ReconnectPolicy::default());
+
+    let resubscriber = client.resubscriber();
+
+    // stop between events, so an update is never cut off halfway through its merge
+    let events = futures::StreamExt::take_until(client.listen_raw(), shutdown.stopped());
+
+    let result = ingest(
+        events,
+        Some(&resubscriber),
+        &state_service,
+        &update_sender,
+        &journal,
+        &upstream,
+        &archive,
+    )
+    .await;
+
+    upstream.disconnected();
+
+    result
+}
+
+/// Apply signalr events to the state and broadcast the updates, independent of
+/// where they come from. Messages are classic frames, archived as they were received.
+/// With a `resubscriber` a new session starts with the full state of a fresh `Subscribe`,
+/// sources that can't subscribe again start it from its `SessionInfo`.
+pub async fn ingest(
+    stream: impl Stream<Item = Event<String>>,
+    resubscriber: Option<&Resubscriber>,
+    state_service: &StateService,
+    update_sender: &Sender<Update>,
+    journal: &Journal,
+    upstream: &Upstream,
+    archive: &Archive,
 ) -> Result<(), Error> {
-    let mut signalr_client = signalr::create_client(URL, HUB).await?;
+    tokio::pin!(stream);
+
+    // set while waiting for the state of a new session, which includes the updates until then
+    let mut awaiting_state = false;
+
+    while let Some(event) = stream.next().await {
+        let items = match event {
+            Event::Subscribed(initial) => {
+                upstream.connected();
+                awaiting_state = false;
+
+                initial_received(&initial);
+
+                if let Some(name) = initial.pointer("/SessionInfo/Name").and_then(Value::as_str) {
+                    upstream.session(name);
+                }
+
+                if let Some(status) = initial
+                    .pointer("/SessionStatus/Status")
+                    .and_then(Value::as_str)
+                {
+                    upstream.session_status(status);
+                }
 
-    let initial = signalr::subscribe(&mut signalr_client, &TOPICS).await?;
-    handle_initial(&state_service, initial).await?;
+                // after a longer disconnect the feed can already be in the next session
+                let current = current_session(state_service).await;
 
-    let mut stream = signalr::listen(signalr_client);
+                match Session::from_state(&initial) {
+                    Some(next)
+                        if current
+                            .as_ref()
+                            .is_some_and(|current| current.id != next.id) =>
+                    {
+                        start_session(
+                            update_sender,
+                            state_service,
+                            journal,
+                            archive,
+                            current,
+                            initial,
+                        )
+                        .await?
+                    }
+                    _ => {
+                        archive.initial(&initial).await;
+                        handle_initial(update_sender, state_service, journal, initial).await?
+                    }
+                }
+
+                continue;
+            }
+            Event::Resumed => {
+                info!("resumed connection to f1");
+                upstream.connected();
+                continue;
+            }
+            Event::Reconnecting {
+                attempt,
+                delay,
+                reason,
+            } => {
+                warn!(attempt, ?delay, %reason, "lost connection to f1, reconnecting");
+                upstream.disconnected();
+                continue;
+            }
+            Event::Failed(err) => return Err(err.into()),
+            Event::Message(frame) => {
+                // frames of the new session are part of the state it starts with
+                if !awaiting_state {
+                    archive.frame(&frame).await;
+                }
+
+                let Some(items) = parse_updates(&frame) else {
+                    trace!("ignoring frame without updates");
+                    continue;
+                };
+
+                // counted before any update is skipped, the bytes arrived either way
+                frame_received(frame.len(), &items);
+
+                items
+            }
+        };
 
-    while let Some(items) = stream.next().await {
         for update in items {
             trace!(?update.topic, "Received data for topic");
 
-            if update.topic == "SessionInfo" && update.data.pointer("/Name").is_some() {
-                warn!("received SessionInfo event, restarting...");
-                return Ok(());
+            upstream.received(&update.topic);
+
+            if update.topic == "SessionStatus"
+                && let Some(status) = update.data.pointer("/Status").and_then(Value::as_str)
+                && upstream.session_status(status)
+            {
+                info!(status, "session status changed");
+            }
+
+            if awaiting_state {
+                trace!(?update.topic, "waiting for the state of the new session, skipping update");
+                continue;
+            }
+
+            if update.topic == "SessionInfo"
+                && let Some(next) = Session::from_info(&update.data)
+            {
+                if let Some(name) = &next.name {
+                    upstream.session(name);
+                }
+
+                let current = current_session(state_service).await;
+
+                if current
+                    .as_ref()
+                    .is_some_and(|current| current.id != next.id)
+                {
+                    if let Some(resubscriber) = resubscriber {
+                        info!(
+                            next = next.id,
+                            "session changed, subscribing again for its state"
+                        );
+                        resubscriber.resubscribe();
+                        awaiting_state = true;
+                        continue;
+                    }
+
+                    // nothing of the old session carries over, not even the rest of its SessionInfo
+                    let mut state = Value::Object(Map::new());
+                    merge(&mut state, json!({ "SessionInfo": update.data }));
+
+                    start_session(
+                        update_sender,
+                        state_service,
+                        journal,
+                        archive,
+                        current,
+                        state,
+                    )
+                    .await?;
+                    continue;
+                }
             }
 
-            match handle_update(&update_sender, &state_service, update.topic, update.data).await {
+            match handle_update(update_sender, state_service, journal, update).await {
                 Ok(_) => trace!("handled update"),
                 Err(err) => error!(?err, "failed to handle update"),
             };
@@ -59,26 +226,141 @@ pub async fn ingest_f1(
     Ok(())
 }
 
+/// Count a frame for the topics of its updates, a frame with several splits its bytes
+/// evenly between them.
+fn frame_received(bytes: usize, items: &[UpdateArgs]) {
+    let share = bytes / items.len().max(1);
+
+    for update in items {
+        metrics::received(&update.topic, share);
+        metrics::feed_latency(update.timestamp);
+    }
+}
+
+/// Count the initial state per topic. The client parses the `Subscribe` response,
+/// so its topics are measured serialized again.
+fn initial_received(initial: &Value) {
+    let Some(topics) = initial.as_object() else {
+        return;
+    };
+
+    for (topic, data) in topics {
+        metrics::received(topic, data.to_string().len());
+    }
+}
+
+/// The session of the current state.
+async fn current_session(state_service: &StateService) -> Option<Session> {
+    Session::from_info(&state_service.get_topic("SessionInfo").await?)
+}
+
+/// Archive the state the previous session ended with and replace it with the state of
+/// the new one. Clients get the new state as a `session`, so they never merge updates
+/// of the new session into the old one.
+async fn start_session(
+    sender: &Sender<Update>,
+    state_service: &StateService,
+    journal: &Journal,
+    archive: &Archive,
+    previous: Option<Session>,
+    state: Value,
+) -> Result<(), Error> {
+    let next = Session::from_state(&state);
+
+    info!(
+        previous = previous.as_ref().map(|session| session.id.as_str()),
+        next = next.as_ref().map(|session| session.id.as_str()),
+        "session changed"
+    );
+
+    let finished = state_service.get_state().await?;
+    archive.final_state(previous.as_ref(), &finished).await;
+    archive.session(&state).await;
+
+    let data: Arc<str> = state.to_string().into();
+
+    let seq = journal.session(state.clone()).await;
+    state_service.set_state(state, seq).await?;
+
+    let update = Update {
+        seq,
+        kind: UpdateKind::Session,
+        data,
+    };
+
+    match sender.send(update) {
+        Ok(_) => trace!("sent session to realtime channel"),
+        Err(err) => error!(?err, "failed to send session to realtime channel"),
+    };
+
+    Ok(())
+}
+
 async fn handle_update(
-    sender: &Sender<String>,
+    sender: &Sender<Update>,
     state_service: &StateService,
-    topic: String,
-    update: Value,
+    journal: &Journal,
+    update: UpdateArgs,
 ) -> Result<(), Error> {
-    let update = json!({ topic: update });
+    let timestamp = update
+        .timestamp
+        .to_rfc3339_opts(SecondsFormat::Millis, true);
+
+    let mut update = json!({ &update.topic: update.data, TIMESTAMP_KEY: timestamp });
+
+    let message: Arc<str> = update.to_string().into();
+
+    // the timestamp only travels with the update, it's not part of the state
+    if let Some(update) = update.as_object_mut() {
+        update.remove(TIMESTAMP_KEY);
+    }
+
+    // snapshots carry the number of the last update merged into them,
+    // clients skip everything up to it
+    let seq = journal.push(message.clone()).await;
+
+    let merge = Instant::now();
+    state_service.update_state(update, seq).await?;
+    metrics::merged(merge.elapsed());
+
+    let update = Update {
+        seq,
+        kind: UpdateKind::Update,
+        data: message,
+    };
 
-    match sender.send(update.to_string()) {
+    match sender.send(update) {
         Ok(_) => trace!("sent update to realtime channel"),
         Err(err) => error!(?err, "failed to send update to realtime channel"),
     };
 
-    state_service.update_state(update).await?;
-
     Ok(())
 }
 
-async fn handle_initial(state_service: &StateService, initial: Value) -> Result<(), Error> {
+async fn handle_initial(
+    sender: &Sender<Update>,
+    state_service: &StateService,
+    journal: &Journal,
+    initial: Value,
+) -> Result<(), Error> {
     trace!("handling initial state");
-    state_service.set_state(initial).await?;
+
+    let data: Arc<str> = initial.to_string().into();
+
+    let seq = journal.reset(initial.clone()).await;
+    state_service.set_state(initial, seq).await?;
+
+    // connected clients would otherwise keep merging into the state from before the gap
+    let update = Update {
+        seq,
+        kind: UpdateKind::Initial,
+        data,
+    };
+
+    match sender.send(update) {
+        Ok(_) => trace!("sent initial to realtime channel"),
+        Err(err) => error!(?err, "failed to send initial to realtime channel"),
+    };
+
     Ok(())
 }
@@ -1,59 +1,108 @@
-use std::{env, sync::Arc};
+use std::sync::Arc;
 
 use anyhow::Error;
 use axum::{
     Router,
     http::{HeaderValue, Method},
+    response::sse::KeepAlive,
     routing::get,
 };
+use metrics_exporter_prometheus::PrometheusHandle;
 use tokio::{net::TcpListener, sync::broadcast::Sender};
 use tower_http::cors::CorsLayer;
 use tracing::info;
 
-use crate::services::state_service::StateService;
+use crate::{
+    config::Config,
+    services::{
+        journal::{Journal, Update},
+        shutdown::Shutdown,
+        state_service::StateService,
+        subscribers::Subscribers,
+        upstream::Upstream,
+    },
+};
 
 mod connections;
 mod current;
+mod delayed;
 mod drivers;
 mod health;
+mod metrics;
 mod realtime;
+mod topics;
+mod ws;
 
 pub struct Context {
     pub state_service: StateService,
-    pub tx: Sender<String>,
+    pub tx: Sender<Update>,
+    pub journal: Journal,
+    pub subscribers: Subscribers,
+    pub upstream: Upstream,
+    pub metrics: PrometheusHandle,
+    pub shutdown: Shutdown,
+    pub keep_alive: KeepAlive,
 }
 
-pub async fn start(state_service: StateService, tx: Sender<String>) -> Result<(), Error> {
-    let addr = env::var("ADDRESS").unwrap_or_else(|_| "0.0.0.0:80".to_string());
+pub async fn start(
+    config: Config,
+    state_service: StateService,
+    tx: Sender<Update>,
+    journal: Journal,
+    upstream: Upstream,
+    metrics: PrometheusHandle,
+    shutdown: Shutdown,
+) -> Result<(), Error> {
+    let addr = config.address;
+
+    let keep_alive = KeepAlive::new()
+        .text(config.keep_alive.text.as_str())
+        .interval(config.keep_alive.interval());
 
-    let context = Arc::new(Context { state_service, tx });
+    let context = Arc::new(Context {
+        state_service,
+        tx,
+        journal,
+        subscribers: Subscribers::default(),
+        upstream,
+        metrics,
+        shutdown: shutdown.clone(),
+        keep_alive,
+    });
 
-    let cors = cors_layer()?;
+    let cors = cors_layer(&config.origins)?;
 
     let app = Router::new()
         .route("/api/health", get(health::health_check))
+        .route("/api/ready", get(health::ready))
         .route("/api/realtime", get(realtime::sse_stream))
+        .route("/api/ws", get(ws::ws_stream))
         .route("/api/current", get(current::current_state))
         .route("/api/drivers", get(drivers::drivers))
         .route("/api/connections", get(connections::current_connections))
+        .route("/metrics", get(metrics::metrics))
         .with_state(context)
         .layer(cors)
         .into_make_service();
 
     info!(addr, "starting norths http server");
 
-    axum::serve(TcpListener::bind(addr).await?, app).await?;
+    // stop accepting connections, open streams end themselves with a reconnect hint
+    let mut listener = shutdown.listen();
+    let stopped = async move { listener.stopped().await };
+
+    axum::serve(TcpListener::bind(addr).await?, app)
+        .with_graceful_shutdown(stopped)
+        .await?;
 
     Ok(())
 }
 
-pub fn cors_layer() -> Result<CorsLayer, Error> {
-    let origin = env::var("ORIGIN").unwrap_or_else(|_| "https://f1-dash.com".to_string());
-
-    let origins = origin
-        .split(';')
-        .filter_map(|o| HeaderValue::from_str(o).ok())
-        .collect::<Vec<HeaderValue>>();
+pub fn cors_layer(origins: &[String]) -> Result<CorsLayer, Error> {
+    let origins = origins
+        .iter()
+        .map(|origin| HeaderValue::from_str(origin))
+        .collect::<Result<Vec<HeaderValue>, _>>()?;
 
     Ok(CorsLayer::new()
         .allow_origin(origins)
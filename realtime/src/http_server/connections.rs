@@ -3,17 +3,22 @@ use std::sync::Arc;
 use axum::{extract::State, http::StatusCode, response::IntoResponse};
 use serde::Serialize;
 
-use crate::http_server::Context;
+use crate::{http_server::Context, services::subscribers::SubscriberInfo};
 
-#[derive(Debug, Serialize)]
+#[derive(Serialize)]
 struct ConnectionsResponse {
     connections: usize,
+    subscribers: Vec<SubscriberInfo>,
 }
 
 pub async fn current_connections(State(ctx): State<Arc<Context>>) -> impl IntoResponse {
     let connections = ctx.tx.receiver_count();
+    let subscribers = ctx.subscribers.list();
 
-    let response = ConnectionsResponse { connections };
+    let response = ConnectionsResponse {
+        connections,
+        subscribers,
+    };
 
     (StatusCode::OK, axum::Json(response))
 }
@@ -0,0 +1,80 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use async_stream::stream;
+use axum::response::sse::Event;
+use futures::Stream;
+use serde_json::Value;
+use tokio::time::{Instant, sleep_until};
+use tracing::{debug, warn};
+
+use crate::{
+    http_server::{Context, realtime::entry_event, topics::TopicFilter},
+    services::journal::Next,
+};
+
+/// Replays the journal `delay` behind the live feed, starting from the state as of
+/// now minus `delay`, so clients get a delayed view without buffering themselves.
+/// A client that reconnects with `last_event_id` continues after that entry instead.
+pub fn stream(
+    ctx: Arc<Context>,
+    filter: TopicFilter,
+    delay: Duration,
+    last_event_id: Option<u64>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream! {
+        let _subscriber = ctx.subscribers.register("sse-delayed");
+        let mut recorded = ctx.journal.subscribe();
+
+        let resume = match last_event_id {
+            Some(id) if !matches!(ctx.journal.next(id).await, Next::Evicted) => Some(id),
+            _ => None,
+        };
+
+        let mut seq = match resume {
+            Some(id) => id,
+            None => {
+                let (state, seq) = ctx.journal.snapshot_at(delayed_now(delay)).await;
+                yield Ok(initial(&filter, state, seq));
+                seq
+            }
+        };
+
+        loop {
+            match ctx.journal.next(seq).await {
+                Next::Entry(entry) => {
+                    sleep_until(entry.time + delay).await;
+                    seq = entry.seq;
+
+                    if let Some(event) = entry_event(&filter, entry) {
+                        yield Ok(event);
+                    }
+                }
+                Next::Pending => {
+                    if recorded.changed().await.is_err() {
+                        debug!("journal closed, ending delayed stream");
+                        break;
+                    }
+                }
+                Next::Evicted => {
+                    warn!(seq, "delayed stream fell out of the journal, resyncing");
+
+                    let (state, latest) = ctx.journal.snapshot_at(delayed_now(delay)).await;
+                    seq = latest;
+                    yield Ok(initial(&filter, state, seq));
+                }
+            }
+        }
+    }
+}
+
+fn delayed_now(delay: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_sub(delay).unwrap_or(now)
+}
+
+fn initial(filter: &TopicFilter, state: Value, seq: u64) -> Event {
+    Event::default()
+        .event("initial")
+        .id(seq.to_string())
+        .data(filter.filter_value(state).to_string())
+}
@@ -1,13 +1,99 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use chrono::{DateTime, Utc};
 use serde::Serialize;
 
-#[derive(Debug, Serialize)]
+use crate::http_server::Context;
+
+/// Not ready when nothing arrived for this long. Shorter than the f1 idle timeout
+/// (`upstream.idle_timeout`, 5 minutes by default), so a stalled feed takes the instance
+/// out of rotation before the client reconnects on its own.
+const STALE_AFTER: chrono::Duration = chrono::Duration::seconds(120);
+
+#[derive(Serialize)]
 struct HealthCheckResponse {
     service: bool,
+    upstream: UpstreamHealth,
+    subscribers: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpstreamHealth {
+    connected: bool,
+    last_connected: Option<DateTime<Utc>>,
+    reconnects: u64,
+    session: Option<String>,
+    session_status: Option<String>,
+    /// Seconds since the last message of each topic.
+    topics: BTreeMap<String, f64>,
 }
 
-pub async fn health_check() -> impl IntoResponse {
-    let response = HealthCheckResponse { service: true };
+#[derive(Serialize)]
+struct ReadyResponse {
+    ready: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+}
+
+pub async fn health_check(State(ctx): State<Arc<Context>>) -> impl IntoResponse {
+    let status = ctx.upstream.status();
+    let now = Utc::now();
+
+    let topics = status
+        .topics
+        .into_iter()
+        .map(|(topic, received)| (topic, age(now, received)))
+        .collect();
+
+    let response = HealthCheckResponse {
+        service: true,
+        upstream: UpstreamHealth {
+            connected: status.connected,
+            last_connected: status.last_connected,
+            reconnects: status.reconnects,
+            session: status.session_name,
+            session_status: status.session_status,
+            topics,
+        },
+        subscribers: ctx.subscribers.count(),
+    };
 
     (StatusCode::OK, axum::Json(response))
 }
+
+/// Fails while the upstream is disconnected, its data is stale or the server shuts down,
+/// so traffic goes to replicas that still receive updates.
+pub async fn ready(State(ctx): State<Arc<Context>>) -> impl IntoResponse {
+    let status = ctx.upstream.status();
+
+    // a fresh connection counts as data, quiet sessions still send a heartbeat soon after
+    let last_data = status.last_message.max(status.last_connected);
+
+    let reason = if ctx.shutdown.is_triggered() {
+        Some("shutting down")
+    } else if !status.connected {
+        Some("upstream disconnected")
+    } else if last_data.is_none_or(|last| Utc::now() - last > STALE_AFTER) {
+        Some("upstream data is stale")
+    } else {
+        None
+    };
+
+    let code = match reason {
+        Some(_) => StatusCode::SERVICE_UNAVAILABLE,
+        None => StatusCode::OK,
+    };
+
+    let response = ReadyResponse {
+        ready: reason.is_none(),
+        reason,
+    };
+
+    (code, axum::Json(response))
+}
+
+fn age(now: DateTime<Utc>, time: DateTime<Utc>) -> f64 {
+    (now - time).num_milliseconds() as f64 / 1000.0
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use tracing::error;
+
+use crate::{http_server::Context, services::metrics};
+
+/// Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub async fn metrics(State(ctx): State<Arc<Context>>) -> impl IntoResponse {
+    // cached per state version, so this only serializes when new clients would too
+    let snapshot_bytes = match ctx.state_service.get_state_string().await {
+        Ok(state) => state.len(),
+        Err(err) => {
+            error!(?err, "failed to get state for metrics");
+            0
+        }
+    };
+
+    let upstream = ctx.upstream.status();
+    metrics::scraped(snapshot_bytes, upstream.connected, upstream.reconnects);
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, CONTENT_TYPE)],
+        ctx.metrics.render(),
+    )
+}
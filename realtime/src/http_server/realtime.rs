@@ -1,58 +1,247 @@
-use std::{convert::Infallible, sync::Arc};
-
-use tokio_stream::wrappers::BroadcastStream;
+use std::{convert::Infallible, sync::Arc, time::Duration};
 
+use async_stream::stream;
 use axum::{
-    extract::State,
-    response::{
-        Sse,
-        sse::{Event, KeepAlive},
-    },
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Sse, sse::Event},
 };
 use futures::{Stream, StreamExt, stream};
-use tracing::{debug, error, info};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    http_server::{
+        Context, delayed,
+        topics::{TopicFilter, TopicsQuery},
+    },
+    services::journal::{Entry, EntryKind, MAX_DELAY, UpdateKind},
+};
 
-use crate::http_server::Context;
+#[derive(Deserialize)]
+pub struct DelayQuery {
+    /// Seconds the stream lags behind the live feed.
+    delay: Option<u64>,
+}
+
+/// Header browsers send when an `EventSource` reconnects, with the `id` of the last event.
+const LAST_EVENT_ID: &str = "last-event-id";
 
 pub async fn sse_stream(
     State(ctx): State<Arc<Context>>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Query(query): Query<TopicsQuery>,
+    Query(DelayQuery { delay }): Query<DelayQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
     debug!("sse stream starting");
 
-    let initial_state = ctx
-        .state_service
-        .get_state_string()
-        .await
-        .unwrap_or_else(|e| {
-            error!(?e, "failed to get initial state");
-            "{}".to_string()
-        });
+    let filter = TopicFilter::new(&query);
+    let keep_alive = ctx.keep_alive.clone();
+
+    let last_event_id = headers
+        .get(LAST_EVENT_ID)
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse::<u64>().ok());
 
+    match delay.map(Duration::from_secs) {
+        Some(delay) if delay > MAX_DELAY => Err((
+            StatusCode::BAD_REQUEST,
+            format!("delay can be at most {} seconds", MAX_DELAY.as_secs()),
+        )),
+        Some(delay) if !delay.is_zero() => {
+            info!(?delay, "starting delayed stream");
+            let stream = delayed::stream(ctx.clone(), filter, delay, last_event_id);
+            let stream = until_shutdown(&ctx, stream.left_stream());
+            Ok(Sse::new(stream).keep_alive(keep_alive))
+        }
+        _ => {
+            let stream = live_stream(ctx.clone(), filter, last_event_id).await;
+            let stream = until_shutdown(&ctx, stream.right_stream());
+            Ok(Sse::new(stream).keep_alive(keep_alive))
+        }
+    }
+}
+
+/// Starts with the updates missed since `last_event_id` when the journal still has all
+/// of them, otherwise with the current state, and continues with the broadcast updates.
+async fn live_stream(
+    ctx: Arc<Context>,
+    filter: TopicFilter,
+    last_event_id: Option<u64>,
+) -> impl Stream<Item = Result<Event, Infallible>> + use<> {
+    // subscribe first, updates that also made it into the journal or state are skipped below
     let connections = ctx.tx.receiver_count();
     let rx = ctx.tx.subscribe();
 
     info!(?connections, "connection stats");
 
-    let initial = stream::once(async {
-        debug!("streaming current initial");
-        Ok(Event::default().event("initial").data(initial_state))
-    });
-
-    let updates = BroadcastStream::new(rx)
-        .filter_map(|result| async move {
-            match result {
-                Ok(msg) => Some(msg),
-                Err(e) => {
-                    error!(?e, "broadcast stream error");
-                    None
+    let missed = match last_event_id {
+        Some(id) => ctx.journal.since(id).await.map(|entries| (id, entries)),
+        None => None,
+    };
+
+    let (head, mut last_seq) = match missed {
+        Some((id, entries)) => {
+            debug!(id, missed = entries.len(), "resuming stream");
+
+            let last_seq = entries.last().map_or(id, |entry| entry.seq);
+            let events = entries
+                .into_iter()
+                .filter_map(|entry| entry_event(&filter, entry))
+                .collect();
+
+            (events, last_seq)
+        }
+        None => {
+            if let Some(id) = last_event_id {
+                debug!(id, "missed updates are gone, sending the current state");
+            }
+
+            debug!("streaming current initial");
+            let (seq, initial) = snapshot_event(&ctx, &filter).await;
+
+            (vec![initial], seq)
+        }
+    };
+
+    let subscriber = ctx.subscribers.register("sse");
+
+    let updates = stream! {
+        let mut rx = rx;
+
+        loop {
+            match rx.recv().await {
+                Ok(update) if update.seq <= last_seq => {}
+                Ok(update) => {
+                    last_seq = update.seq;
+
+                    match update.kind {
+                        UpdateKind::Initial => {
+                            let data = filter.filter_message(update.data).unwrap_or_else(|| "{}".into());
+                            yield initial_event(update.seq, &data);
+                        }
+                        UpdateKind::Update => {
+                            if let Some(data) = filter.filter_message(update.data) {
+                                yield update_event(update.seq, data);
+                            }
+                        }
+                        // like initials sent even without any selected topics, the old state has to go
+                        UpdateKind::Session => {
+                            let data = filter.filter_message(update.data).unwrap_or_else(|| "{}".into());
+                            yield session_event(update.seq, &data);
+                        }
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(id = subscriber.id(), skipped, "sse client lagged behind, resyncing");
+                    subscriber.lagged(skipped);
+
+                    let (seq, initial) = snapshot_event(&ctx, &filter).await;
+                    last_seq = seq;
+                    yield initial;
                 }
+                Err(RecvError::Closed) => break,
             }
-        })
-        .map(|data| Event::default().event("update").data(data))
-        .map(Ok);
+        }
+    };
+
+    stream::iter(head).chain(updates).map(Ok)
+}
+
+/// Ends `events` with a `reconnect` event once the server shuts down. Its `retry`
+/// makes `EventSource` wait the hint before reconnecting, by then to another replica.
+fn until_shutdown<S>(
+    ctx: &Context,
+    events: S,
+) -> impl Stream<Item = Result<Event, Infallible>> + use<S>
+where
+    S: Stream<Item = Result<Event, Infallible>>,
+{
+    let mut shutdown = ctx.shutdown.listen();
+    let hint = ctx.shutdown.reconnect_hint();
+
+    stream! {
+        tokio::pin!(events);
+
+        loop {
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => yield event,
+                    None => break,
+                },
+                _ = shutdown.stopped() => {
+                    debug!("server shutting down, sending reconnect");
+
+                    yield Ok(Event::default()
+                        .event("reconnect")
+                        .retry(hint)
+                        .data(json!({ "delay": hint.as_millis() }).to_string()));
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// An `initial` event with the current state and the sequence number it includes.
+async fn snapshot_event(ctx: &Context, filter: &TopicFilter) -> (u64, Event) {
+    let (seq, state) = initial_state(ctx, filter).await;
+
+    (seq, initial_event(seq, &state))
+}
+
+/// The event for a journal entry, `None` when its topics are filtered out.
+pub fn entry_event(filter: &TopicFilter, entry: Entry) -> Option<Event> {
+    match entry.kind {
+        EntryKind::Initial(state) => Some(initial_event(
+            entry.seq,
+            &filter.filter_value(Arc::unwrap_or_clone(state)).to_string(),
+        )),
+        EntryKind::Session(state) => Some(session_event(
+            entry.seq,
+            &filter.filter_value(Arc::unwrap_or_clone(state)).to_string(),
+        )),
+        EntryKind::Update(data) => filter
+            .filter_message(data)
+            .map(|data| update_event(entry.seq, data)),
+    }
+}
+
+/// The complete state, replacing whatever the client had.
+fn initial_event(seq: u64, state: &str) -> Event {
+    Event::default()
+        .event("initial")
+        .id(seq.to_string())
+        .data(state)
+}
+
+/// The complete state of a new session, replacing the one of the previous session.
+fn session_event(seq: u64, state: &str) -> Event {
+    Event::default()
+        .event("session")
+        .id(seq.to_string())
+        .data(state)
+}
+
+fn update_event(seq: u64, data: Arc<str>) -> Event {
+    Event::default()
+        .event("update")
+        .id(seq.to_string())
+        .data(data)
+}
 
-    let stream = initial.chain(updates);
-    let keep_alive = KeepAlive::new().text("keep-alive-text");
+/// The current state trimmed to the topics of `filter`, and the
+/// sequence number of the last update it includes.
+pub async fn initial_state(ctx: &Context, filter: &TopicFilter) -> (u64, Arc<str>) {
+    if !filter.is_all() {
+        let (seq, state) = ctx.state_service.get_state_seq().await;
+        return (seq, filter.filter_value(state).to_string().into());
+    }
 
-    Sse::new(stream).keep_alive(keep_alive)
+    ctx.state_service.get_snapshot().await.unwrap_or_else(|e| {
+        error!(?e, "failed to get initial state");
+        (ctx.journal.latest(), "{}".into())
+    })
 }
@@ -0,0 +1,202 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use serde::{Deserialize, de::IgnoredAny};
+use serde_json::Value;
+use signalr::COMPRESSED_SUFFIX;
+
+/// Query of the streaming endpoints, both lists are comma separated topic names.
+/// Compressed topics match with or without their suffix, `CarData` also selects `CarData.z`.
+#[derive(Deserialize, Default)]
+pub struct TopicsQuery {
+    /// Only send these topics.
+    pub topics: Option<String>,
+    /// Never send these topics.
+    pub exclude: Option<String>,
+}
+
+/// Decides which topics a client receives. Keys starting with `_`,
+/// like the update timestamp, aren't topics and are always kept.
+#[derive(Debug, Clone, Default)]
+pub struct TopicFilter {
+    include: Option<HashSet<String>>,
+    exclude: HashSet<String>,
+}
+
+impl TopicFilter {
+    pub fn new(query: &TopicsQuery) -> Self {
+        Self {
+            include: query.topics.as_deref().map(parse_list),
+            exclude: query.exclude.as_deref().map(parse_list).unwrap_or_default(),
+        }
+    }
+
+    /// Filter from already split lists, `None` includes all topics.
+    pub fn from_lists(include: Option<Vec<String>>, exclude: Vec<String>) -> Self {
+        Self {
+            include: include.map(|topics| topics.into_iter().collect()),
+            exclude: exclude.into_iter().collect(),
+        }
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.include.is_none() && self.exclude.is_empty()
+    }
+
+    pub fn allows(&self, key: &str) -> bool {
+        if key.starts_with('_') {
+            return true;
+        }
+
+        let name = key.strip_suffix(COMPRESSED_SUFFIX).unwrap_or(key);
+        let matches = |set: &HashSet<String>| set.contains(key) || set.contains(name);
+
+        self.include.as_ref().is_none_or(matches) && !matches(&self.exclude)
+    }
+
+    /// Trim a state object down to the allowed topics.
+    pub fn filter_value(&self, value: Value) -> Value {
+        match value {
+            Value::Object(map) if !self.is_all() => Value::Object(
+                map.into_iter()
+                    .filter(|(key, _)| self.allows(key))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    /// Trim a serialized state or update, `None` when none of its topics are allowed.
+    /// Messages whose topics are all allowed are passed on without re-serializing.
+    pub fn filter_message(&self, message: Arc<str>) -> Option<Arc<str>> {
+        if self.is_all() {
+            return Some(message);
+        }
+
+        // only look at the keys, the values can be large
+        let Ok(keys) = serde_json::from_str::<HashMap<String, IgnoredAny>>(&message) else {
+            return Some(message);
+        };
+
+        let topics = keys.keys().filter(|key| !key.starts_with('_'));
+        let allowed = topics.clone().filter(|key| self.allows(key)).count();
+
+        if allowed == 0 {
+            return None;
+        }
+
+        if allowed == topics.count() {
+            return Some(message);
+        }
+
+        let value = serde_json::from_str(&message).ok()?;
+        Some(self.filter_value(value).to_string().into())
+    }
+}
+
+fn parse_list(list: &str) -> HashSet<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|topic| !topic.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn filter(topics: Option<&str>, exclude: Option<&str>) -> TopicFilter {
+        TopicFilter::new(&TopicsQuery {
+            topics: topics.map(str::to_string),
+            exclude: exclude.map(str::to_string),
+        })
+    }
+
+    fn message(value: Value) -> Arc<str> {
+        value.to_string().into()
+    }
+
+    #[test]
+    fn compressed_topics_match_without_their_suffix() {
+        let filter = filter(Some("CarData, TimingData,"), Some("Position"));
+
+        assert!(filter.allows("CarData"));
+        assert!(filter.allows("CarData.z"));
+        assert!(filter.allows("TimingData"));
+        assert!(!filter.allows("Position.z"));
+        assert!(!filter.allows("WeatherData"));
+    }
+
+    #[test]
+    fn underscore_keys_always_pass() {
+        let filter = filter(Some("TimingData"), Some("_timestamp"));
+
+        assert!(filter.allows("_timestamp"));
+        assert!(filter.allows("_kf"));
+    }
+
+    #[test]
+    fn passes_all_messages_without_a_selection() {
+        let filter = filter(None, None);
+        let update = message(json!({ "WeatherData": {} }));
+
+        assert!(filter.is_all());
+        assert!(Arc::ptr_eq(
+            &filter.filter_message(update.clone()).expect("kept"),
+            &update
+        ));
+    }
+
+    #[test]
+    fn keeps_fully_allowed_messages_as_they_are() {
+        let filter = filter(Some("TimingData"), None);
+        let update =
+            message(json!({ "TimingData": { "Lines": {} }, "_timestamp": "2024-05-01T12:00:00Z" }));
+
+        let filtered = filter.filter_message(update.clone()).expect("kept");
+        assert!(Arc::ptr_eq(&filtered, &update));
+    }
+
+    #[test]
+    fn trims_partially_allowed_messages() {
+        let filter = filter(None, Some("CarData"));
+        let update = message(json!({
+            "CarData.z": "7ZQ9...",
+            "TimingData": { "Lines": {} },
+            "_timestamp": "2024-05-01T12:00:00Z"
+        }));
+
+        let filtered = filter.filter_message(update).expect("kept");
+        let filtered: Value = serde_json::from_str(&filtered).expect("json");
+
+        assert_eq!(
+            filtered,
+            json!({ "TimingData": { "Lines": {} }, "_timestamp": "2024-05-01T12:00:00Z" })
+        );
+    }
+
+    #[test]
+    fn drops_messages_without_allowed_topics() {
+        let filter = filter(Some("TimingData"), None);
+        let update =
+            message(json!({ "CarData.z": "7ZQ9...", "_timestamp": "2024-05-01T12:00:00Z" }));
+
+        assert_eq!(filter.filter_message(update), None);
+    }
+
+    #[test]
+    fn trims_states_to_the_allowed_topics() {
+        let filter = TopicFilter::from_lists(Some(vec!["Heartbeat".to_string()]), Vec::new());
+        let state = json!({ "Heartbeat": {}, "TimingData": {}, "_kf": true });
+
+        assert_eq!(
+            filter.filter_value(state),
+            json!({ "Heartbeat": {}, "_kf": true })
+        );
+    }
+}
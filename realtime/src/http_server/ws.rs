@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        Query, State, WebSocketUpgrade,
+        ws::{CloseFrame, Message, WebSocket, close_code},
+    },
+    response::Response,
+};
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    http_server::{
+        Context,
+        realtime::initial_state,
+        topics::{TopicFilter, TopicsQuery},
+    },
+    services::journal::UpdateKind,
+};
+
+/// Commands a client sends as JSON text messages.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Command {
+    /// Replace the topic filter, `topics` missing or `null` selects all topics.
+    /// Answered with a fresh `initial` for the new selection.
+    Topics {
+        topics: Option<Vec<String>>,
+        #[serde(default)]
+        exclude: Vec<String>,
+    },
+    /// Request the current state as `initial` again.
+    Snapshot,
+    /// Answered with a `pong`.
+    Ping,
+}
+
+/// Same `initial`, `session` and `update` messages as the SSE stream, wrapped as
+/// `{"type":"initial","seq":1,"data":{...}}` to share one socket with the commands.
+/// `seq` is the event id of the SSE stream, an `initial` includes every update up to it.
+pub async fn ws_stream(
+    ws: WebSocketUpgrade,
+    State(ctx): State<Arc<Context>>,
+    Query(query): Query<TopicsQuery>,
+) -> Response {
+    debug!("ws stream starting");
+
+    let filter = TopicFilter::new(&query);
+
+    ws.on_upgrade(|socket| handle_ws(socket, ctx, filter))
+}
+
+async fn handle_ws(mut socket: WebSocket, ctx: Arc<Context>, mut filter: TopicFilter) {
+    let mut rx = ctx.tx.subscribe();
+    let mut shutdown = ctx.shutdown.listen();
+    let subscriber = ctx.subscribers.register("ws");
+
+    info!(connections = ctx.tx.receiver_count(), "connection stats");
+
+    // updates already in the state are skipped below, they were subscribed to first
+    let Ok(mut last_seq) = send_initial(&mut socket, &ctx, &filter).await else {
+        return;
+    };
+
+    loop {
+        let result = tokio::select! {
+            _ = shutdown.stopped() => {
+                debug!("server shutting down, sending reconnect");
+                send_reconnect(&mut socket, &ctx).await;
+                break;
+            }
+            update = rx.recv() => match update {
+                Ok(update) if update.seq <= last_seq => Ok(()),
+                Ok(update) => {
+                    last_seq = update.seq;
+
+                    match (update.kind, filter.filter_message(update.data)) {
+                        (UpdateKind::Update, Some(data)) => {
+                            send(&mut socket, "update", update.seq, &data).await
+                        }
+                        (UpdateKind::Update, None) => Ok(()),
+                        (UpdateKind::Initial, state) => {
+                            send(&mut socket, "initial", update.seq, state.as_deref().unwrap_or("{}")).await
+                        }
+                        (UpdateKind::Session, state) => {
+                            send(&mut socket, "session", update.seq, state.as_deref().unwrap_or("{}")).await
+                        }
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(id = subscriber.id(), skipped, "ws client lagged behind, resyncing");
+                    subscriber.lagged(skipped);
+                    send_initial(&mut socket, &ctx, &filter)
+                        .await
+                        .map(|seq| last_seq = seq)
+                }
+                Err(RecvError::Closed) => break,
+            },
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    handle_command(&mut socket, &ctx, &mut filter, &mut last_seq, &text).await
+                }
+                Some(Ok(Message::Close(_))) | None => break,
+                Some(Ok(_)) => Ok(()),
+                Some(Err(err)) => {
+                    debug!(?err, "ws receive error");
+                    break;
+                }
+            },
+        };
+
+        if let Err(err) = result {
+            debug!(?err, "ws send error");
+            break;
+        }
+    }
+
+    info!("ws connection closed");
+}
+
+async fn handle_command(
+    socket: &mut WebSocket,
+    ctx: &Context,
+    filter: &mut TopicFilter,
+    last_seq: &mut u64,
+    text: &str,
+) -> Result<(), axum::Error> {
+    let command = match serde_json::from_str::<Command>(text) {
+        Ok(command) => command,
+        Err(err) => {
+            let error = serde_json::json!({ "type": "error", "message": err.to_string() });
+            return socket.send(Message::text(error.to_string())).await;
+        }
+    };
+
+    match command {
+        Command::Topics { topics, exclude } => {
+            *filter = TopicFilter::from_lists(topics, exclude);
+            *last_seq = send_initial(socket, ctx, filter).await?;
+            Ok(())
+        }
+        Command::Snapshot => {
+            *last_seq = send_initial(socket, ctx, filter).await?;
+            Ok(())
+        }
+        Command::Ping => socket.send(Message::text(r#"{"type":"pong"}"#)).await,
+    }
+}
+
+/// Send the current state, returns the sequence number of the last update it includes.
+async fn send_initial(
+    socket: &mut WebSocket,
+    ctx: &Context,
+    filter: &TopicFilter,
+) -> Result<u64, axum::Error> {
+    let (seq, initial) = initial_state(ctx, filter).await;
+
+    send(socket, "initial", seq, &initial)
+        .await
+        .inspect_err(|err| error!(?err, "failed to send initial state"))?;
+
+    Ok(seq)
+}
+
+/// Ask the client to reconnect after the hint, then close as a restart.
+async fn send_reconnect(socket: &mut WebSocket, ctx: &Context) {
+    let delay = ctx.shutdown.reconnect_hint().as_millis();
+    let reconnect = serde_json::json!({ "type": "reconnect", "delay": delay });
+
+    let _ = socket.send(Message::text(reconnect.to_string())).await;
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code: close_code::RESTART,
+            reason: "server shutting down".into(),
+        })))
+        .await;
+}
+
+/// `data` is already serialized JSON and embedded as is.
+async fn send(socket: &mut WebSocket, kind: &str, seq: u64, data: &str) -> Result<(), axum::Error> {
+    let message = format!(r#"{{"type":"{kind}","seq":{seq},"data":{data}}}"#);
+    socket.send(Message::text(message)).await
+}
@@ -0,0 +1,15 @@
+pub mod config;
+pub mod f1;
+pub mod http_server;
+pub mod relay;
+pub mod replay;
+pub mod services {
+    pub mod archive;
+    pub mod journal;
+    pub mod metrics;
+    pub mod session;
+    pub mod shutdown;
+    pub mod state_service;
+    pub mod subscribers;
+    pub mod upstream;
+}
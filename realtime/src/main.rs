@@ -1,43 +1,170 @@
-use anyhow::Error;
-use shared::tracing_subscriber;
-use tokio::sync::broadcast;
-use tracing::warn;
+use anyhow::{Error, anyhow};
+use shared::{config::UpstreamConfig, shutdown_signal, tracing_subscriber};
+use tokio::{sync::broadcast, time::timeout};
+use tracing::{info, warn};
 
-use crate::services::state_service::StateService;
-
-mod f1;
-mod http_server;
-mod services {
-    pub mod state_service;
-}
+use realtime::{
+    config::Config,
+    f1, http_server, relay, replay,
+    services::{
+        archive::Archive,
+        journal::{Journal, MAX_DELAY, Update},
+        shutdown::Shutdown,
+        state_service::StateService,
+        upstream::Upstream,
+    },
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber();
 
+    let config = Config::load()?;
+    let metrics = realtime::services::metrics::install()?;
+
     let state_service = StateService::new();
+    let journal = Journal::new(MAX_DELAY);
+    let upstream = Upstream::default();
+    let shutdown = Shutdown::new(config.shutdown.reconnect_hint());
+    let archive = Archive::spawn(&config.archive, shutdown.listen())?;
+    let source = Source::new(&config)?;
+    let restart = config.restart.policy();
+    let deadline = config.shutdown.deadline();
 
-    let (sender, _) = broadcast::channel::<String>(16);
+    let (sender, _) = broadcast::channel::<Update>(config.broadcast_capacity);
 
-    {
+    let mut ingest = {
         let state_service = state_service.clone();
         let sender = sender.clone();
+        let journal = journal.clone();
+        let upstream = upstream.clone();
+        let mut listener = shutdown.listen();
         tokio::spawn(async move {
+            let mut failures: u32 = 0;
+
             loop {
-                match f1::ingest_f1(state_service.clone(), sender.clone()).await {
-                    Ok(_) => {}
+                let result = match &source {
+                    Source::Replay(replay) => {
+                        replay::ingest_replay(
+                            replay,
+                            state_service.clone(),
+                            sender.clone(),
+                            journal.clone(),
+                            upstream.clone(),
+                            archive.clone(),
+                            &mut listener,
+                        )
+                        .await
+                    }
+                    Source::Relay(relay) => {
+                        relay::ingest_relay(
+                            relay,
+                            state_service.clone(),
+                            sender.clone(),
+                            journal.clone(),
+                            upstream.clone(),
+                            archive.clone(),
+                            &mut listener,
+                        )
+                        .await
+                    }
+                    Source::F1(config) => {
+                        f1::ingest_f1(
+                            config,
+                            state_service.clone(),
+                            sender.clone(),
+                            journal.clone(),
+                            upstream.clone(),
+                            archive.clone(),
+                            &mut listener,
+                        )
+                        .await
+                    }
+                };
+
+                match result {
+                    Ok(_) => failures = 0,
                     Err(err) => {
-                        warn!(?err, "ingest_f1 method returned error");
+                        failures += 1;
+                        warn!(?err, failures, "ingest returned error");
                     }
                 };
 
-                warn!("ingest_f1 method returned, possible session change, restarting...");
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                if listener.is_stopped() {
+                    break;
+                }
+
+                if restart.max_attempts.is_some_and(|max| failures > max) {
+                    return Err(anyhow!(
+                        "ingest failed {failures} times in a row, giving up"
+                    ));
+                }
+
+                let delay = restart.delay(failures.max(1));
+                warn!(?delay, "ingest returned, restarting...");
+
+                tokio::select! {
+                    _ = listener.stopped() => break,
+                    _ = tokio::time::sleep(delay) => {}
+                }
             }
-        });
+
+            info!("ingest stopped");
+            Ok::<_, Error>(())
+        })
+    };
+
+    let mut server = tokio::spawn(http_server::start(
+        config,
+        state_service,
+        sender,
+        journal,
+        upstream,
+        metrics,
+        shutdown.clone(),
+    ));
+
+    tokio::select! {
+        result = &mut server => return result?,
+        result = &mut ingest => return result?,
+        _ = shutdown_signal() => {}
     }
 
-    http_server::start(state_service, sender).await?;
+    info!(?deadline, "shutting down, draining clients");
+
+    shutdown.trigger();
+
+    let drained = async {
+        let _ = server.await;
+        let _ = ingest.await;
+        shutdown.drained().await;
+    };
+
+    match timeout(deadline, drained).await {
+        Ok(_) => info!("shutdown complete"),
+        Err(_) => warn!("shutdown deadline passed, exiting anyway"),
+    }
 
     Ok(())
 }
+
+/// Where the data comes from, the f1 feed unless a recording or another instance is set.
+enum Source {
+    F1(UpstreamConfig),
+    Replay(replay::Replay),
+    Relay(relay::Relay),
+}
+
+impl Source {
+    fn new(config: &Config) -> Result<Self, Error> {
+        if let Some(replay) = replay::Replay::new(&config.replay) {
+            return Ok(Source::Replay(replay));
+        }
+
+        if let Some(relay) = relay::Relay::new(&config.relay)? {
+            return Ok(Source::Relay(relay));
+        }
+
+        Ok(Source::F1(config.upstream.clone()))
+    }
+}
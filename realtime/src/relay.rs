@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use anyhow::{Error, anyhow};
+use async_stream::stream;
+use chrono::{SecondsFormat, Utc};
+use futures::{Stream, StreamExt};
+use reqwest::{Client, header};
+use serde_json::{Value, json};
+use signalr::{Event, ReconnectPolicy};
+use tokio::{sync::broadcast::Sender, time::timeout};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::RelayConfig,
+    f1::{TIMESTAMP_KEY, ingest},
+    services::{
+        archive::Archive,
+        journal::{Journal, Update},
+        shutdown::Listener,
+        state_service::StateService,
+        upstream::Upstream,
+    },
+};
+use sse::{SseEvent, SseParser};
+
+pub mod sse;
+
+/// Hub of the frames relayed updates are passed on in, like in recordings.
+const HUB: &str = "Streaming";
+
+/// Give up on opening a connection to the other instance after this long.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Another realtime instance whose SSE stream is relayed instead of connecting to f1.
+pub struct Relay {
+    url: String,
+    client: Client,
+    policy: ReconnectPolicy,
+    /// Reconnect when not even a keep-alive arrived for this long.
+    idle_timeout: Duration,
+}
+
+impl Relay {
+    /// The stream of `config`, `None` without an url.
+    pub fn new(config: &RelayConfig) -> Result<Option<Self>, Error> {
+        let Some(url) = config.url.clone() else {
+            return Ok(None);
+        };
+
+        let client = Client::builder().connect_timeout(CONNECT_TIMEOUT).build()?;
+
+        Ok(Some(Self {
+            url,
+            client,
+            policy: ReconnectPolicy::default(),
+            idle_timeout: config.idle_timeout(),
+        }))
+    }
+}
+
+/// Apply the stream of the other instance through [`ingest`], `initial` and `session`
+/// events as the initial state and `update` events as updates. Lost connections are resumed with
+/// `Last-Event-ID`, so the other instance sends the missed updates or a fresh state.
+pub async fn ingest_relay(
+    relay: &Relay,
+    state_service: StateService,
+    update_sender: Sender<Update>,
+    journal: Journal,
+    upstream: Upstream,
+    archive: Archive,
+    shutdown: &mut Listener,
+) -> Result<(), Error> {
+    info!(url = relay.url, "relaying realtime instance");
+
+    let events = relay_events(relay, upstream.clone());
+
+    let result = ingest(
+        events.take_until(shutdown.stopped()),
+        None,
+        &state_service,
+        &update_sender,
+        &journal,
+        &upstream,
+        &archive,
+    )
+    .await;
+
+    upstream.disconnected();
+
+    result
+}
+
+fn relay_events<'a>(
+    relay: &'a Relay,
+    upstream: Upstream,
+) -> impl Stream<Item = Event<String>> + 'a {
+    stream! {
+        let mut last_event_id: Option<String> = None;
+        let mut attempt: u32 = 0;
+
+        loop {
+            let mut request = relay.client.get(&relay.url).header(header::ACCEPT, "text/event-stream");
+
+            if let Some(id) = &last_event_id {
+                request = request.header("Last-Event-ID", id);
+            }
+
+            // how long to wait before reconnecting, `None` uses the reconnect policy
+            let mut hint: Option<Duration> = None;
+
+            // an instance that accepts the connection but never answers is retried like any other failure
+            let response = match timeout(relay.idle_timeout, request.send()).await {
+                Ok(response) => response
+                    .and_then(|response| response.error_for_status())
+                    .map_err(Error::from),
+                Err(_) => Err(anyhow!("no response within {:?}", relay.idle_timeout)),
+            };
+
+            match response {
+                Ok(response) => {
+                    debug!(resume = ?last_event_id, "connected to realtime instance");
+
+                    // the other instance continues after our last event, or sends an initial
+                    if last_event_id.is_some() {
+                        yield Event::Resumed;
+                    }
+
+                    let mut body = response.bytes_stream();
+                    let mut parser = SseParser::default();
+
+                    let reason = loop {
+                        let chunk = match timeout(relay.idle_timeout, body.next()).await {
+                            Ok(Some(Ok(chunk))) => chunk,
+                            Ok(Some(Err(err))) => break err.to_string(),
+                            Ok(None) => break "stream ended".to_string(),
+                            Err(_) => break format!("no data received for {:?}", relay.idle_timeout),
+                        };
+
+                        let relayed = parser.push(&chunk);
+
+                        if let Some(id) = relayed.iter().rev().find_map(|event| event.id.clone()) {
+                            last_event_id = Some(id);
+                        }
+
+                        // events were parsed, so the connection works
+                        if !relayed.is_empty() {
+                            attempt = 0;
+                        }
+
+                        let mut shutting_down = false;
+
+                        for complete in relayed {
+                            match complete.event.as_deref() {
+                                Some("reconnect") => {
+                                    hint = complete.retry;
+                                    shutting_down = true;
+                                    break;
+                                }
+                                _ => {
+                                    if let Some(event) = ingest_event(complete) {
+                                        yield event;
+                                    }
+                                }
+                            }
+                        }
+
+                        if shutting_down {
+                            break "instance shutting down".to_string();
+                        }
+                    };
+
+                    warn!(reason, "lost connection to realtime instance");
+                }
+                Err(err) => warn!(?err, "failed to connect to realtime instance"),
+            }
+
+            upstream.disconnected();
+
+            attempt += 1;
+            let delay = hint.unwrap_or_else(|| relay.policy.delay(attempt));
+
+            info!(attempt, ?delay, "reconnecting to realtime instance");
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// What ingest makes of a relayed event, `None` for events it doesn't need.
+fn ingest_event(event: SseEvent) -> Option<Event<String>> {
+    let kind = event.event.as_deref();
+
+    if !matches!(kind, Some("initial" | "session" | "update")) {
+        return None;
+    }
+
+    let data = serde_json::from_str::<Value>(&event.data())
+        .inspect_err(|err| warn!(?err, "failed to parse relayed event"))
+        .ok()?;
+
+    match kind {
+        // ingest tells a new session from its SessionInfo and passes it on as such
+        Some("initial" | "session") => Some(Event::Subscribed(data)),
+        Some("update") => {
+            let Value::Object(mut update) = data else {
+                return None;
+            };
+
+            // the timestamp as the other instance received it, only parsed again by ingest
+            let timestamp = match update.remove(TIMESTAMP_KEY) {
+                Some(Value::String(timestamp)) => timestamp,
+                _ => Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            };
+
+            let messages = update
+                .into_iter()
+                .map(
+                    |(topic, data)| json!({ "H": HUB, "M": "feed", "A": [topic, data, timestamp] }),
+                )
+                .collect::<Vec<Value>>();
+
+            let frame = json!({ "M": messages }).to_string();
+
+            Some(Event::Message(frame))
+        }
+        _ => None,
+    }
+}
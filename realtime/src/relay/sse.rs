@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+/// A server-sent event, only the fields realtime sends.
+#[derive(Debug, Default, PartialEq)]
+pub struct SseEvent {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: Vec<String>,
+    pub retry: Option<Duration>,
+}
+
+/// Splits a response body into events, chunks can end anywhere in a line.
+#[derive(Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: SseEvent,
+}
+
+impl SseParser {
+    /// The events completed by `chunk`. Blank lines that only end a keep-alive
+    /// comment don't count as events.
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line = self.buffer.drain(..=end).collect::<Vec<u8>>();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if !line.is_empty() {
+                self.event.field(line);
+                continue;
+            }
+
+            let event = std::mem::take(&mut self.event);
+
+            if event != SseEvent::default() {
+                events.push(event);
+            }
+        }
+
+        events
+    }
+}
+
+impl SseEvent {
+    /// The data lines joined back together.
+    pub fn data(&self) -> String {
+        self.data.join("\n")
+    }
+
+    fn field(&mut self, line: &str) {
+        // lines starting with a colon are comments, like the keep-alives
+        if line.starts_with(':') {
+            return;
+        }
+
+        let (name, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+
+        match name {
+            "event" => self.event = Some(value.to_string()),
+            "id" => self.id = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            "retry" => self.retry = value.parse().ok().map(Duration::from_millis),
+            _ => {}
+        }
+    }
+}
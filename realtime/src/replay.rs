@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Error};
+use async_stream::stream;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::Value;
+use signalr::{Event, FileTransport, SignalrClient};
+use tokio::sync::broadcast::Sender;
+use tracing::info;
+
+use crate::{
+    config::ReplayConfig,
+    f1::ingest,
+    services::{
+        archive::Archive,
+        journal::{Journal, Update},
+        shutdown::Listener,
+        state_service::StateService,
+        upstream::Upstream,
+    },
+};
+
+/// Recordings answer any hub and subscription.
+const HUB: &str = "Streaming";
+
+/// A recording made with `simulator save`, replayed instead of connecting to f1.
+pub struct Replay {
+    path: PathBuf,
+    speed: f64,
+}
+
+/// The fresh state a recording has after a reconnect.
+#[derive(Deserialize)]
+struct Resubscribed {
+    #[serde(rename = "R")]
+    r: Value,
+}
+
+impl Replay {
+    /// The recording of `config`, `None` without a file.
+    pub fn new(config: &ReplayConfig) -> Option<Self> {
+        Some(Self {
+            path: config.file.clone()?,
+            speed: config.speed,
+        })
+    }
+}
+
+/// Play the recording through [`ingest`] like the live feed, the first line as the
+/// initial state and every following frame at its recorded pace. Returns at the end
+/// of the recording, which the restart loop then plays again.
+pub async fn ingest_replay(
+    replay: &Replay,
+    state_service: StateService,
+    update_sender: Sender<Update>,
+    journal: Journal,
+    upstream: Upstream,
+    archive: Archive,
+    shutdown: &mut Listener,
+) -> Result<(), Error> {
+    let transport = FileTransport::open(&replay.path)
+        .await
+        .with_context(|| format!("failed to open {}", replay.path.display()))?
+        .paced(replay.speed);
+
+    let mut client = SignalrClient::from_transport(HUB, transport);
+    // the recording decides the topics, `Subscribe` only returns its first line
+    let initial = signalr::subscribe(&mut client, &[]).await?;
+
+    info!(path = %replay.path.display(), speed = replay.speed, "replaying recording");
+
+    let frames = signalr::listen_raw(client);
+
+    let events = stream! {
+        yield Event::Subscribed(initial);
+
+        for await frame in frames {
+            yield frame_event(frame);
+        }
+    };
+
+    let result = ingest(
+        events.take_until(shutdown.stopped()),
+        None,
+        &state_service,
+        &update_sender,
+        &journal,
+        &upstream,
+        &archive,
+    )
+    .await;
+
+    upstream.disconnected();
+    info!("recording finished");
+
+    result
+}
+
+/// A resubscribed state, or the frame as it is, for ingest to pick the updates from.
+fn frame_event(frame: String) -> Event<String> {
+    match serde_json::from_str::<Resubscribed>(&frame) {
+        Ok(resubscribed) => Event::Subscribed(resubscribed.r),
+        Err(_) => Event::Message(frame),
+    }
+}
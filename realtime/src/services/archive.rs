@@ -0,0 +1,282 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Context, Error};
+use chrono::Utc;
+use serde_json::{Value, json};
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+    sync::mpsc,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::ArchiveConfig,
+    services::{session::Session, shutdown::Listener},
+};
+
+/// Frames queued for the writer, ingest only waits for it once it falls this far behind.
+const QUEUE_CAPACITY: usize = 4096;
+
+/// Where finished sessions are kept, nothing is written without a directory.
+///
+/// Every ingested update is also recorded to a file per session, in the format of
+/// `simulator save`: the initial state, then every frame as it was received, and
+/// `{"R": state}` after a resubscribe. Recordings can be played again with `F1_REPLAY_FILE`.
+/// Relays only get the updates of the other instance, recorded as a frame per event.
+///
+/// Recordings are written by their own task, so syncing them to disk never holds up ingest.
+#[derive(Clone)]
+pub struct Archive {
+    dir: Option<PathBuf>,
+    writer: Option<mpsc::Sender<Command>>,
+}
+
+/// What the writer task records, in the order ingest received it.
+enum Command {
+    Initial(Value),
+    Session(Value),
+    Frame(String),
+}
+
+/// Owns the file the current session is recorded to.
+struct Writer {
+    dir: PathBuf,
+    recording: Option<Recording>,
+}
+
+/// The file the current session is recorded to.
+struct Recording {
+    /// Id of the recorded session, `None` when its state had no `SessionInfo`.
+    session: Option<String>,
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl Archive {
+    /// Creates the directory, so a wrong path fails at startup and not once a session ends,
+    /// and starts the writer. It syncs the recording to disk every interval and closes it once
+    /// the server shuts down, holding `shutdown` until then so the last frames aren't lost.
+    pub fn spawn(config: &ArchiveConfig, shutdown: Listener) -> Result<Self, Error> {
+        let Some(dir) = &config.dir else {
+            return Ok(Self {
+                dir: None,
+                writer: None,
+            });
+        };
+
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create archive {}", dir.display()))?;
+
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+
+        let writer = Writer {
+            dir: dir.clone(),
+            recording: None,
+        };
+
+        tokio::spawn(writer.run(rx, config.sync_interval(), shutdown));
+
+        Ok(Self {
+            dir: Some(dir.clone()),
+            writer: Some(tx),
+        })
+    }
+
+    /// Write the state a session ended with as `<session>.final.json`.
+    /// Failures are only logged, they must not hold up the next session.
+    pub async fn final_state(&self, session: Option<&Session>, state: &Value) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+
+        let path = dir.join(format!("{}.final.json", file_stem(session)));
+
+        let result = match serde_json::to_vec(state) {
+            Ok(json) => tokio::fs::write(&path, json).await.map_err(Error::from),
+            Err(err) => Err(err.into()),
+        };
+
+        match result {
+            Ok(()) => info!(path = %path.display(), "archived final session state"),
+            Err(err) => {
+                warn!(?err, path = %path.display(), "failed to archive final session state")
+            }
+        }
+    }
+
+    /// Record a state received after (re)subscribing. Continues the recording when it's
+    /// still the same session, like after a reconnect, otherwise starts a new one.
+    pub async fn initial(&self, state: &Value) {
+        self.send(|| Command::Initial(state.clone())).await;
+    }
+
+    /// Close the recording of the previous session and start one for the new session.
+    pub async fn session(&self, state: &Value) {
+        self.send(|| Command::Session(state.clone())).await;
+    }
+
+    /// Record a frame of the feed as it was received.
+    pub async fn frame(&self, frame: &str) {
+        self.send(|| Command::Frame(frame.to_string())).await;
+    }
+
+    /// Waits only when the writer fell a whole queue behind.
+    async fn send(&self, command: impl FnOnce() -> Command) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+
+        if writer.send(command()).await.is_err() {
+            debug!("archive writer stopped, not recording");
+        }
+    }
+}
+
+impl Writer {
+    async fn run(
+        mut self,
+        mut commands: mpsc::Receiver<Command>,
+        sync_interval: Duration,
+        mut shutdown: Listener,
+    ) {
+        let mut ticker = tokio::time::interval(sync_interval);
+
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(command) => self.handle(command).await,
+                    None => break,
+                },
+                _ = ticker.tick() => self.sync().await,
+                _ = shutdown.stopped() => break,
+            }
+        }
+
+        // ingest stops at shutdown too, write what it sent until then
+        commands.close();
+        while let Some(command) = commands.recv().await {
+            self.handle(command).await;
+        }
+
+        if let Some(recording) = self.recording.take() {
+            recording.close().await;
+        }
+    }
+
+    async fn handle(&mut self, command: Command) {
+        match command {
+            Command::Initial(state) => self.initial(&state).await,
+            Command::Session(state) => {
+                let session = Session::from_state(&state);
+                self.rotate(session.as_ref(), &state).await;
+            }
+            Command::Frame(frame) => self.write(&frame, "failed to record frame").await,
+        }
+    }
+
+    async fn initial(&mut self, state: &Value) {
+        let session = Session::from_state(state);
+
+        match &self.recording {
+            Some(current) if current.session == session.as_ref().map(|s| s.id.clone()) => {
+                let line = json!({ "R": state }).to_string();
+                self.write(&line, "failed to record state").await;
+            }
+            _ => self.rotate(session.as_ref(), state).await,
+        }
+    }
+
+    /// Stops the recording when it can't be written, rather than leaving a gap in it.
+    async fn write(&mut self, line: &str, message: &str) {
+        let Some(current) = self.recording.as_mut() else {
+            return;
+        };
+
+        if let Err(err) = current.write(line).await {
+            warn!(?err, path = %current.path.display(), "{message}, stopping the recording");
+            self.recording = None;
+        }
+    }
+
+    async fn rotate(&mut self, session: Option<&Session>, state: &Value) {
+        if let Some(previous) = self.recording.take() {
+            previous.close().await;
+        }
+
+        // started at is part of the name, so a restart in the same session doesn't overwrite it
+        let started = Utc::now().format("%Y%m%dT%H%M%SZ");
+        let path = self
+            .dir
+            .join(format!("{}_{started}.txt", file_stem(session)));
+
+        match Recording::create(path.clone(), session, state).await {
+            Ok(created) => {
+                info!(path = %path.display(), "recording session");
+                self.recording = Some(created);
+            }
+            Err(err) => warn!(?err, path = %path.display(), "failed to start recording"),
+        }
+    }
+
+    async fn sync(&mut self) {
+        let Some(current) = self.recording.as_mut() else {
+            return;
+        };
+
+        match current.sync().await {
+            Ok(()) => debug!(path = %current.path.display(), "synced recording"),
+            Err(err) => {
+                warn!(?err, path = %current.path.display(), "failed to sync recording, stopping it");
+                self.recording = None;
+            }
+        }
+    }
+}
+
+impl Recording {
+    async fn create(
+        path: PathBuf,
+        session: Option<&Session>,
+        state: &Value,
+    ) -> Result<Self, Error> {
+        let file = File::create(&path).await?;
+
+        let mut recording = Self {
+            session: session.map(|session| session.id.clone()),
+            path,
+            writer: BufWriter::new(file),
+        };
+
+        recording.write(&state.to_string()).await?;
+
+        Ok(recording)
+    }
+
+    async fn write(&mut self, line: &str) -> Result<(), Error> {
+        self.writer.write_all(line.as_bytes()).await?;
+        self.writer.write_all(b"\n").await?;
+        Ok(())
+    }
+
+    async fn sync(&mut self) -> Result<(), Error> {
+        self.writer.flush().await?;
+        self.writer.get_ref().sync_data().await?;
+        Ok(())
+    }
+
+    async fn close(mut self) {
+        match self.sync().await {
+            Ok(()) => info!(path = %self.path.display(), "closed recording"),
+            Err(err) => warn!(?err, path = %self.path.display(), "failed to close recording"),
+        }
+    }
+}
+
+/// Named after the session, or when it was archived without a `SessionInfo`.
+fn file_stem(session: Option<&Session>) -> String {
+    match session {
+        Some(session) => session.file_stem(),
+        None => format!("unknown_{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+    }
+}
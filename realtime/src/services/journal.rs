@@ -0,0 +1,262 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::Value;
+use tokio::{
+    sync::{RwLock, watch},
+    time::Instant,
+};
+use tracing::warn;
+
+use crate::{f1::TIMESTAMP_KEY, services::state_service::merge};
+
+/// How far back the journal reaches, and with it the longest possible delay.
+pub const MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// An update as broadcast to the connected clients.
+#[derive(Clone, Debug)]
+pub struct Update {
+    /// Sequence number of the update in the [`Journal`].
+    pub seq: u64,
+    pub kind: UpdateKind,
+    /// Shared between all receivers instead of copied into each channel,
+    /// every stream still copies it into the event it encodes.
+    pub data: Arc<str>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpdateKind {
+    /// The state was replaced after (re)subscribing, `data` is the complete state.
+    Initial,
+    /// Merged into the state of the current session.
+    Update,
+    /// A new session started, `data` is its complete state and replaces the old one.
+    Session,
+}
+
+/// Cheap to clone, so entries can be copied out of the journal and used without its lock.
+#[derive(Clone)]
+pub enum EntryKind {
+    /// The state was replaced, like after (re)subscribing.
+    Initial(Arc<Value>),
+    /// An update as it was broadcast, including its timestamp.
+    Update(Arc<str>),
+    /// A new session started with this state.
+    Session(Arc<Value>),
+}
+
+#[derive(Clone)]
+pub struct Entry {
+    pub seq: u64,
+    pub time: Instant,
+    pub kind: EntryKind,
+}
+
+/// Result of looking up the entry that follows a sequence number.
+pub enum Next {
+    Entry(Entry),
+    /// Nothing newer was recorded yet.
+    Pending,
+    /// The entry already fell out of the journal.
+    Evicted,
+}
+
+struct Inner {
+    /// State before the first retained entry, shared with snapshots being built.
+    base: Arc<Value>,
+    entries: VecDeque<Entry>,
+    next_seq: u64,
+}
+
+/// Time-indexed record of recent initial states and updates, used to
+/// reconstruct the state at a point in the past and replay from there.
+///
+/// Entries get increasing sequence numbers which start at the current time in
+/// microseconds, so numbers handed out before a restart are never reused.
+#[derive(Clone)]
+pub struct Journal {
+    inner: Arc<RwLock<Inner>>,
+    latest: Arc<watch::Sender<u64>>,
+    retention: Duration,
+}
+
+impl Journal {
+    pub fn new(retention: Duration) -> Self {
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(1, |since| since.as_micros() as u64);
+
+        Self {
+            inner: Arc::new(RwLock::new(Inner {
+                base: Arc::new(Value::Object(serde_json::Map::new())),
+                entries: VecDeque::new(),
+                next_seq: start,
+            })),
+            latest: Arc::new(watch::Sender::new(start - 1)),
+            retention,
+        }
+    }
+
+    /// Record a replaced state and return its sequence number.
+    pub async fn reset(&self, state: Value) -> u64 {
+        self.record(EntryKind::Initial(Arc::new(state))).await
+    }
+
+    /// Record the start of a new session and return its sequence number.
+    pub async fn session(&self, state: Value) -> u64 {
+        self.record(EntryKind::Session(Arc::new(state))).await
+    }
+
+    /// Record an update and return its sequence number.
+    pub async fn push(&self, update: Arc<str>) -> u64 {
+        self.record(EntryKind::Update(update)).await
+    }
+
+    async fn record(&self, kind: EntryKind) -> u64 {
+        let now = Instant::now();
+        let mut inner = self.inner.write().await;
+
+        while let Some(entry) = inner.entries.front() {
+            if now.duration_since(entry.time) <= self.retention {
+                break;
+            }
+
+            if let Some(entry) = inner.entries.pop_front() {
+                // copies the base only while a snapshot still holds on to it
+                apply(Arc::make_mut(&mut inner.base), entry.kind);
+            }
+        }
+
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+        inner.entries.push_back(Entry {
+            seq,
+            time: now,
+            kind,
+        });
+
+        drop(inner);
+        self.latest.send_replace(seq);
+
+        seq
+    }
+
+    /// Sequence number of the last recorded entry.
+    pub fn latest(&self) -> u64 {
+        *self.latest.borrow()
+    }
+
+    /// Notified whenever an entry is recorded.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.latest.subscribe()
+    }
+
+    /// The state as of `time` and the sequence number of the last entry it includes.
+    /// Only the entries are copied while holding the lock, the state is built after.
+    pub async fn snapshot_at(&self, time: Instant) -> (Value, u64) {
+        let (base, entries, seq) = {
+            let inner = self.inner.read().await;
+
+            let entries = inner
+                .entries
+                .iter()
+                .take_while(|entry| entry.time <= time)
+                .cloned()
+                .collect::<Vec<Entry>>();
+
+            let seq = match entries.last() {
+                Some(entry) => entry.seq,
+                None => inner.entries.front().map_or(inner.next_seq, |e| e.seq) - 1,
+            };
+
+            (inner.base.clone(), entries, seq)
+        };
+
+        // everything before the last replaced state doesn't matter
+        let start = entries
+            .iter()
+            .rposition(|entry| matches!(entry.kind, EntryKind::Initial(_) | EntryKind::Session(_)));
+
+        let (mut state, entries) = match start {
+            Some(start) => (Value::Null, &entries[start..]),
+            None => (Value::clone(&base), &entries[..]),
+        };
+
+        for entry in entries {
+            apply(&mut state, entry.kind.clone());
+        }
+
+        (state, seq)
+    }
+
+    /// All entries recorded after `seq`, `None` when some of them were already evicted
+    /// or `seq` wasn't handed out by this journal.
+    pub async fn since(&self, seq: u64) -> Option<Vec<Entry>> {
+        let inner = self.inner.read().await;
+
+        let first = inner
+            .entries
+            .front()
+            .map_or(inner.next_seq, |entry| entry.seq);
+
+        // `seq` comes from clients, one that can't be followed was never handed out
+        let after = seq.checked_add(1)?;
+
+        if after < first || seq >= inner.next_seq {
+            return None;
+        }
+
+        Some(
+            inner
+                .entries
+                .iter()
+                .filter(|entry| entry.seq > seq)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// The entry recorded right after `seq`.
+    pub async fn next(&self, seq: u64) -> Next {
+        let inner = self.inner.read().await;
+
+        let first = inner
+            .entries
+            .front()
+            .map_or(inner.next_seq, |entry| entry.seq);
+
+        let Some(after) = seq.checked_add(1) else {
+            return Next::Evicted;
+        };
+
+        if after < first || seq >= inner.next_seq {
+            return Next::Evicted;
+        }
+
+        match inner.entries.get((after - first) as usize) {
+            Some(entry) => Next::Entry(entry.clone()),
+            None => Next::Pending,
+        }
+    }
+}
+
+fn apply(state: &mut Value, kind: EntryKind) {
+    match kind {
+        EntryKind::Initial(initial) | EntryKind::Session(initial) => {
+            *state = Arc::unwrap_or_clone(initial)
+        }
+        EntryKind::Update(update) => match serde_json::from_str::<Value>(&update) {
+            Ok(mut update) => {
+                if let Some(update) = update.as_object_mut() {
+                    update.remove(TIMESTAMP_KEY);
+                }
+
+                merge(state, update);
+            }
+            Err(err) => warn!(?err, "invalid update in journal"),
+        },
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+const MESSAGES: &str = "realtime_messages_received_total";
+const BYTES: &str = "realtime_bytes_received_total";
+const MERGE: &str = "realtime_merge_duration_seconds";
+const FEED_LATENCY: &str = "realtime_feed_latency_seconds";
+const LAG_EVENTS: &str = "realtime_lag_events_total";
+const SKIPPED: &str = "realtime_skipped_updates_total";
+const CLIENTS: &str = "realtime_clients";
+const SNAPSHOT: &str = "realtime_snapshot_bytes";
+const CONNECTED: &str = "realtime_upstream_connected";
+const RECONNECTS: &str = "realtime_upstream_reconnects_total";
+
+const MERGE_BUCKETS: [f64; 10] = [
+    0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005, 0.01, 0.05, 0.1,
+];
+
+const FEED_LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0];
+
+/// Install the global recorder, every metric recorded before is lost.
+pub fn install() -> Result<PrometheusHandle, Error> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(MERGE.to_string()), &MERGE_BUCKETS)?
+        .set_buckets_for_metric(
+            Matcher::Full(FEED_LATENCY.to_string()),
+            &FEED_LATENCY_BUCKETS,
+        )?
+        .install_recorder()?;
+
+    describe_counter!(MESSAGES, "Messages received from the upstream per topic");
+    describe_counter!(
+        BYTES,
+        "Bytes of the frames received from the upstream per topic"
+    );
+    describe_histogram!(MERGE, "Time to merge an update into the state");
+    describe_histogram!(
+        FEED_LATENCY,
+        "Time between the upstream timestamp of an update and receiving it"
+    );
+    describe_counter!(LAG_EVENTS, "Times a client fell behind the broadcast");
+    describe_counter!(SKIPPED, "Updates clients missed by falling behind");
+    describe_gauge!(CLIENTS, "Connected stream clients per transport");
+    describe_gauge!(SNAPSHOT, "Size of the serialized state sent to new clients");
+    describe_gauge!(CONNECTED, "Whether the upstream is connected");
+    describe_counter!(RECONNECTS, "Times the upstream connection was lost");
+
+    // without an exporter nothing else drains the histograms between scrapes
+    {
+        let handle = handle.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                handle.run_upkeep();
+            }
+        });
+    }
+
+    Ok(handle)
+}
+
+/// Count `bytes` of the upstream for `topic`, as they came off the connection.
+pub fn received(topic: &str, bytes: usize) {
+    counter!(MESSAGES, "topic" => topic.to_string()).increment(1);
+    counter!(BYTES, "topic" => topic.to_string()).increment(bytes as u64);
+}
+
+pub fn feed_latency(timestamp: DateTime<Utc>) {
+    // clocks can be slightly off, an update is never received before it was sent
+    let latency = (Utc::now() - timestamp).to_std().unwrap_or_default();
+    histogram!(FEED_LATENCY).record(latency.as_secs_f64());
+}
+
+pub fn merged(duration: Duration) {
+    histogram!(MERGE).record(duration.as_secs_f64());
+}
+
+pub fn lagged(transport: &'static str, skipped: u64) {
+    counter!(LAG_EVENTS, "transport" => transport).increment(1);
+    counter!(SKIPPED, "transport" => transport).increment(skipped);
+}
+
+pub fn client_connected(transport: &'static str) {
+    gauge!(CLIENTS, "transport" => transport).increment(1);
+}
+
+pub fn client_disconnected(transport: &'static str) {
+    gauge!(CLIENTS, "transport" => transport).decrement(1);
+}
+
+/// Values owned by other services, read when scraped.
+pub fn scraped(snapshot_bytes: usize, connected: bool, reconnects: u64) {
+    gauge!(SNAPSHOT).set(snapshot_bytes as f64);
+    gauge!(CONNECTED).set(if connected { 1.0 } else { 0.0 });
+    counter!(RECONNECTS).absolute(reconnects);
+}
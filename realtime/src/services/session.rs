@@ -0,0 +1,66 @@
+use serde_json::Value;
+
+/// The session a state belongs to, read from its `SessionInfo`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    /// The feed's `Path`, or the meeting and session keys, unique per session.
+    pub id: String,
+    pub meeting: Option<String>,
+    pub name: Option<String>,
+    /// Local start date, `YYYY-MM-DD`.
+    pub date: Option<String>,
+}
+
+impl Session {
+    /// `None` when `info` doesn't identify a session, like updates that only
+    /// change its `ArchiveStatus`.
+    pub fn from_info(info: &Value) -> Option<Self> {
+        let str_at = |pointer: &str| info.pointer(pointer).and_then(Value::as_str);
+
+        let id = match (str_at("/Path"), info.get("Key")) {
+            (Some(path), _) if !path.is_empty() => path.to_string(),
+            (_, Some(key)) if !key.is_null() => {
+                let meeting = info.pointer("/Meeting/Key").unwrap_or(&Value::Null);
+                format!("{meeting}/{key}")
+            }
+            _ => return None,
+        };
+
+        Some(Self {
+            id,
+            meeting: str_at("/Meeting/Name").map(str::to_string),
+            name: str_at("/Name").map(str::to_string),
+            date: str_at("/StartDate")
+                .and_then(|start| start.get(..10))
+                .map(str::to_string),
+        })
+    }
+
+    pub fn from_state(state: &Value) -> Option<Self> {
+        Self::from_info(state.get("SessionInfo")?)
+    }
+
+    /// Date, meeting and session, like `2024-03-02_bahrain-grand-prix_race`.
+    pub fn file_stem(&self) -> String {
+        let parts = [&self.date, &self.meeting, &self.name]
+            .into_iter()
+            .flatten()
+            .map(|part| slug(part))
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<String>>();
+
+        match parts.is_empty() {
+            true => slug(&self.id),
+            false => parts.join("_"),
+        }
+    }
+}
+
+/// Lowercase ascii letters and digits, anything else collapsed into single dashes.
+fn slug(text: &str) -> String {
+    text.to_ascii_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
@@ -0,0 +1,56 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::watch;
+
+/// Tells streams and the ingest loop the server is shutting down,
+/// and tracks when all of them have finished.
+#[derive(Clone)]
+pub struct Shutdown {
+    tx: Arc<watch::Sender<bool>>,
+    reconnect_hint: Duration,
+}
+
+/// Held by everything that has to finish before the process exits.
+pub struct Listener(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// `reconnect_hint` is how long clients should wait before reconnecting elsewhere.
+    pub fn new(reconnect_hint: Duration) -> Self {
+        Self {
+            tx: Arc::new(watch::Sender::new(false)),
+            reconnect_hint,
+        }
+    }
+
+    pub fn reconnect_hint(&self) -> Duration {
+        self.reconnect_hint
+    }
+
+    pub fn trigger(&self) {
+        self.tx.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    pub fn listen(&self) -> Listener {
+        Listener(self.tx.subscribe())
+    }
+
+    /// Resolves once every [`Listener`] was dropped.
+    pub async fn drained(&self) {
+        self.tx.closed().await;
+    }
+}
+
+impl Listener {
+    pub fn is_stopped(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    pub async fn stopped(&mut self) {
+        // the sender lives as long as any `Shutdown`, so this can't fail while one is around
+        let _ = self.0.wait_for(|stopping| *stopping).await;
+    }
+}
@@ -1,17 +1,37 @@
 use anyhow::Error;
 use serde_json::Value;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+use tokio::sync::{Mutex, RwLock};
+use tracing::warn;
+
+/// The state serialized as of a sequence number.
+type Snapshot = Option<(u64, Arc<str>)>;
 
 #[derive(Clone)]
 pub struct StateService {
     state: Arc<RwLock<Value>>,
+    /// Sequence number of the last journal entry applied to the state,
+    /// set while holding the write lock.
+    seq: Arc<AtomicU64>,
+    /// Refreshed on demand, once the sequence number moved on.
+    snapshot: Arc<Mutex<Snapshot>>,
+}
+
+impl Default for StateService {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl StateService {
     pub fn new() -> Self {
         Self {
             state: Arc::new(RwLock::new(Value::Object(serde_json::Map::new()))),
+            seq: Arc::new(AtomicU64::new(0)),
+            snapshot: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -20,42 +40,128 @@ impl StateService {
         Ok(state.clone())
     }
 
-    pub async fn get_state_string(&self) -> Result<String, Error> {
+    /// The state and the sequence number of the last entry it includes.
+    pub async fn get_state_seq(&self) -> (u64, Value) {
         let state = self.state.read().await;
-        Ok(state.to_string())
+        (self.seq.load(Ordering::Acquire), state.clone())
     }
 
-    pub async fn set_state(&self, new_state: Value) -> Result<(), Error> {
+    pub async fn get_topic(&self, topic: &str) -> Option<Value> {
+        let state = self.state.read().await;
+        state.get(topic).cloned()
+    }
+
+    /// The serialized state, shared between callers until the state changes.
+    pub async fn get_state_string(&self) -> Result<Arc<str>, Error> {
+        self.get_snapshot().await.map(|(_, json)| json)
+    }
+
+    /// The serialized state and the sequence number of the last entry it includes.
+    /// Concurrent callers wait for a single serialization instead of each doing their own.
+    pub async fn get_snapshot(&self) -> Result<(u64, Arc<str>), Error> {
+        let mut snapshot = self.snapshot.lock().await;
+        let state = self.state.read().await;
+
+        let seq = self.seq.load(Ordering::Acquire);
+
+        if let Some((cached, json)) = snapshot.as_ref()
+            && *cached == seq
+        {
+            return Ok((seq, json.clone()));
+        }
+
+        let json: Arc<str> = serde_json::to_string(&*state)?.into();
+        *snapshot = Some((seq, json.clone()));
+
+        Ok((seq, json))
+    }
+
+    /// Replace the state with the one recorded as `seq` in the journal.
+    pub async fn set_state(&self, new_state: Value, seq: u64) -> Result<(), Error> {
         let mut state = self.state.write().await;
         *state = new_state;
+        self.seq.store(seq, Ordering::Release);
         Ok(())
     }
 
-    pub async fn update_state(&self, update: Value) -> Result<(), Error> {
+    /// Merge the update recorded as `seq` in the journal.
+    pub async fn update_state(&self, update: Value, seq: u64) -> Result<(), Error> {
         let mut state = self.state.write().await;
         merge(&mut state, update);
+        self.seq.store(seq, Ordering::Release);
         Ok(())
     }
 }
 
+/// Key of the feed's deletion markers, listing the keys to remove.
+pub const DELETED_KEY: &str = "_deleted";
+
+/// How far past its end an update may extend an array. The feed appends elements one
+/// at a time, an index beyond this is garbage and would only allocate a pile of `null`s.
+pub const MAX_ARRAY_GAP: usize = 64;
+
+/// Apply a feed update to the state. Objects are merged recursively, objects with
+/// index keys update arrays in place (padding gaps with `null`), `_deleted` markers
+/// remove object keys before the rest of the update is applied,
+/// and any other value replaces what was there.
 pub fn merge(base: &mut Value, update: Value) {
     match (base, update) {
-        (Value::Object(prev), Value::Object(update)) => {
+        (Value::Object(prev), Value::Object(mut update)) => {
+            if let Some(deleted) = update.remove(DELETED_KEY) {
+                for key in deleted_keys(deleted) {
+                    prev.remove(&key);
+                }
+            }
+
             for (k, v) in update {
                 merge(prev.entry(k).or_insert(Value::Null), v);
             }
         }
+        // `_deleted` isn't an index so it's skipped, whether it removes array
+        // elements isn't confirmed against a real session yet
         (Value::Array(prev), Value::Object(update)) => {
             for (k, v) in update {
-                if let Ok(index) = k.parse::<usize>() {
-                    if let Some(item) = prev.get_mut(index) {
-                        merge(item, v);
-                    } else {
-                        prev.push(v);
-                    }
+                let Ok(index) = k.parse::<usize>() else {
+                    continue;
+                };
+
+                if index >= prev.len() {
+                    let Some(len) = index
+                        .checked_add(1)
+                        .filter(|&len| len - prev.len() <= MAX_ARRAY_GAP)
+                    else {
+                        warn!(
+                            index,
+                            len = prev.len(),
+                            "ignoring update far past the end of an array"
+                        );
+                        continue;
+                    };
+
+                    prev.resize(len, Value::Null);
                 }
+
+                merge(&mut prev[index], v);
             }
         }
+        // a new object still has to lose its markers
+        (base, Value::Object(update)) => {
+            *base = Value::Object(serde_json::Map::new());
+            merge(base, Value::Object(update));
+        }
         (a, b) => *a = b,
     }
 }
+
+fn deleted_keys(deleted: Value) -> impl Iterator<Item = String> {
+    let keys = match deleted {
+        Value::Array(keys) => keys,
+        key => vec![key],
+    };
+
+    keys.into_iter().filter_map(|key| match key {
+        Value::String(key) => Some(key),
+        Value::Number(index) => Some(index.to_string()),
+        _ => None,
+    })
+}
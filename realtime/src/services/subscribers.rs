@@ -0,0 +1,119 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::services::metrics;
+
+/// Clients currently connected to a stream, with how often they fell behind.
+#[derive(Clone, Default)]
+pub struct Subscribers {
+    inner: Arc<Mutex<BTreeMap<u64, Arc<Stats>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+struct Stats {
+    transport: &'static str,
+    connected_at: DateTime<Utc>,
+    lag_events: AtomicU64,
+    skipped_updates: AtomicU64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriberInfo {
+    pub id: u64,
+    pub transport: &'static str,
+    pub connected_at: DateTime<Utc>,
+    /// How often the client lagged behind the broadcast and was resynced.
+    pub lag_events: u64,
+    /// Updates the client missed in total, each lag replaced them with a snapshot.
+    pub skipped_updates: u64,
+}
+
+impl Subscribers {
+    /// Track a new client until the returned guard is dropped.
+    pub fn register(&self, transport: &'static str) -> Subscriber {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let stats = Arc::new(Stats {
+            transport,
+            connected_at: Utc::now(),
+            lag_events: AtomicU64::new(0),
+            skipped_updates: AtomicU64::new(0),
+        });
+
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.insert(id, stats.clone());
+        }
+
+        metrics::client_connected(transport);
+
+        Subscriber {
+            id,
+            stats,
+            subscribers: self.clone(),
+        }
+    }
+
+    /// Number of clients currently connected.
+    pub fn count(&self) -> usize {
+        self.inner.lock().map(|inner| inner.len()).unwrap_or(0)
+    }
+
+    pub fn list(&self) -> Vec<SubscriberInfo> {
+        let Ok(inner) = self.inner.lock() else {
+            return Vec::new();
+        };
+
+        inner
+            .iter()
+            .map(|(id, stats)| SubscriberInfo {
+                id: *id,
+                transport: stats.transport,
+                connected_at: stats.connected_at,
+                lag_events: stats.lag_events.load(Ordering::Relaxed),
+                skipped_updates: stats.skipped_updates.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// A registered client, removed from [`Subscribers`] when dropped.
+pub struct Subscriber {
+    id: u64,
+    stats: Arc<Stats>,
+    subscribers: Subscribers,
+}
+
+impl Subscriber {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Count a lag of `skipped` updates.
+    pub fn lagged(&self, skipped: u64) {
+        self.stats.lag_events.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .skipped_updates
+            .fetch_add(skipped, Ordering::Relaxed);
+
+        metrics::lagged(self.stats.transport, skipped);
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.subscribers.inner.lock() {
+            inner.remove(&self.id);
+        }
+
+        metrics::client_disconnected(self.stats.transport);
+    }
+}
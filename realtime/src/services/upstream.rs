@@ -0,0 +1,83 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use chrono::{DateTime, Utc};
+
+/// What is known about the connection to the upstream feed, for health checks.
+#[derive(Clone, Default)]
+pub struct Upstream {
+    inner: Arc<Mutex<Status>>,
+}
+
+#[derive(Clone, Default)]
+pub struct Status {
+    pub connected: bool,
+    pub last_connected: Option<DateTime<Utc>>,
+    pub last_message: Option<DateTime<Utc>>,
+    /// Connections lost since the service started.
+    pub reconnects: u64,
+    pub session_name: Option<String>,
+    /// `Status` of the last `SessionStatus`, like "Started" or "Finalised".
+    pub session_status: Option<String>,
+    /// When each topic last received a message.
+    pub topics: BTreeMap<String, DateTime<Utc>>,
+}
+
+impl Upstream {
+    pub fn status(&self) -> Status {
+        self.lock().clone()
+    }
+
+    pub fn connected(&self) {
+        let mut status = self.lock();
+        status.connected = true;
+        status.last_connected = Some(Utc::now());
+    }
+
+    pub fn disconnected(&self) {
+        let mut status = self.lock();
+
+        if status.connected {
+            status.reconnects += 1;
+        }
+
+        status.connected = false;
+    }
+
+    pub fn received(&self, topic: &str) {
+        let now = Utc::now();
+        let mut status = self.lock();
+
+        status.last_message = Some(now);
+
+        match status.topics.get_mut(topic) {
+            Some(received) => *received = now,
+            None => {
+                status.topics.insert(topic.to_string(), now);
+            }
+        }
+    }
+
+    pub fn session(&self, name: &str) {
+        self.lock().session_name = Some(name.to_string());
+    }
+
+    /// Record the session status, returns whether it changed.
+    pub fn session_status(&self, status: &str) -> bool {
+        let mut current = self.lock();
+
+        if current.session_status.as_deref() == Some(status) {
+            return false;
+        }
+
+        current.session_status = Some(status.to_string());
+        true
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Status> {
+        // the status stays usable even if a holder panicked
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
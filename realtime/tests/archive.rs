@@ -0,0 +1,200 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use futures::StreamExt;
+use serde_json::{Value, json};
+use signalr::{FileTransport, SignalrClient};
+use tokio::time::{sleep, timeout};
+
+use realtime::{
+    config::ArchiveConfig,
+    services::{archive::Archive, session::Session, shutdown::Shutdown},
+};
+
+/// An empty directory for the archive of test `name`.
+fn archive_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("realtime-archive-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn config(dir: &Path, sync_interval: u64) -> ArchiveConfig {
+    ArchiveConfig {
+        dir: Some(dir.to_path_buf()),
+        sync_interval,
+    }
+}
+
+fn state(path: &str, meeting: &str, name: &str) -> Value {
+    json!({
+        "SessionInfo": {
+            "Path": path,
+            "Name": name,
+            "Meeting": { "Name": meeting },
+            "StartDate": "2024-03-02T18:00:00",
+        },
+        "Heartbeat": { "Utc": "2024-03-02T18:00:00.000Z" },
+    })
+}
+
+fn frame(utc: &str) -> String {
+    json!({ "M": [{ "H": "Streaming", "M": "feed", "A": ["Heartbeat", { "Utc": utc }, utc] }] })
+        .to_string()
+}
+
+/// The one file in `dir` whose name starts with `prefix`.
+fn find(dir: &Path, prefix: &str) -> PathBuf {
+    let mut found = fs::read_dir(dir)
+        .expect("archive directory")
+        .map(|entry| entry.expect("archived file").path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(prefix))
+        })
+        .collect::<Vec<PathBuf>>();
+
+    assert_eq!(found.len(), 1, "files starting with {prefix} in {dir:?}");
+    found.remove(0)
+}
+
+fn lines(path: &Path) -> Vec<Value> {
+    fs::read_to_string(path)
+        .expect("recording")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("a json value per line"))
+        .collect()
+}
+
+/// Waits for the writer to close its recording, like the server on its way out.
+async fn shut_down(shutdown: Shutdown) {
+    shutdown.trigger();
+
+    timeout(Duration::from_secs(5), shutdown.drained())
+        .await
+        .expect("writer stops");
+}
+
+#[tokio::test]
+async fn records_a_file_per_session() {
+    let dir = archive_dir("rotation");
+    let shutdown = Shutdown::new(Duration::ZERO);
+    // never synced on its own, what's on disk was written by the shutdown
+    let archive = Archive::spawn(&config(&dir, 3600), shutdown.listen()).expect("archive");
+
+    let sprint = state("2024/bahrain/sprint/", "Bahrain Grand Prix", "Sprint");
+    let race = state("2024/bahrain/race/", "Bahrain Grand Prix", "Race");
+
+    archive.initial(&sprint).await;
+    archive.frame(&frame("2024-03-02T18:00:01.000Z")).await;
+    // a reconnect in the same session continues its recording
+    archive.initial(&sprint).await;
+    archive.frame(&frame("2024-03-02T18:00:02.000Z")).await;
+
+    archive
+        .final_state(Session::from_state(&sprint).as_ref(), &sprint)
+        .await;
+    archive.session(&race).await;
+    archive.frame(&frame("2024-03-02T19:00:01.000Z")).await;
+
+    shut_down(shutdown).await;
+
+    let sprint_recording = find(&dir, "2024-03-02_bahrain-grand-prix_sprint_");
+    assert_eq!(
+        lines(&sprint_recording),
+        [
+            sprint.clone(),
+            serde_json::from_str::<Value>(&frame("2024-03-02T18:00:01.000Z")).unwrap(),
+            json!({ "R": sprint }),
+            serde_json::from_str::<Value>(&frame("2024-03-02T18:00:02.000Z")).unwrap(),
+        ]
+    );
+
+    let race_recording = find(&dir, "2024-03-02_bahrain-grand-prix_race_");
+    assert_eq!(
+        lines(&race_recording),
+        [
+            race,
+            serde_json::from_str::<Value>(&frame("2024-03-02T19:00:01.000Z")).unwrap(),
+        ]
+    );
+
+    let final_state = find(&dir, "2024-03-02_bahrain-grand-prix_sprint.final.json");
+    let archived: Value = serde_json::from_slice(&fs::read(final_state).unwrap()).unwrap();
+    assert_eq!(archived, sprint);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn syncs_recordings_while_running() {
+    let dir = archive_dir("sync");
+    let shutdown = Shutdown::new(Duration::ZERO);
+    let archive = Archive::spawn(&config(&dir, 1), shutdown.listen()).expect("archive");
+
+    let race = state("2024/bahrain/race/", "Bahrain Grand Prix", "Race");
+
+    archive.initial(&race).await;
+    archive.frame(&frame("2024-03-02T19:00:01.000Z")).await;
+
+    sleep(Duration::from_millis(1500)).await;
+
+    let recording = find(&dir, "2024-03-02_bahrain-grand-prix_race_");
+    assert_eq!(lines(&recording).len(), 2);
+
+    shut_down(shutdown).await;
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn recordings_replay() {
+    let dir = archive_dir("replay");
+    let shutdown = Shutdown::new(Duration::ZERO);
+    let archive = Archive::spawn(&config(&dir, 3600), shutdown.listen()).expect("archive");
+
+    let race = state("2024/bahrain/race/", "Bahrain Grand Prix", "Race");
+    let frames = [
+        frame("2024-03-02T19:00:01.000Z"),
+        frame("2024-03-02T19:00:02.000Z"),
+    ];
+
+    archive.initial(&race).await;
+    for frame in &frames {
+        archive.frame(frame).await;
+    }
+    archive.initial(&race).await;
+
+    shut_down(shutdown).await;
+
+    let recording = find(&dir, "2024-03-02_bahrain-grand-prix_race_");
+    let transport = FileTransport::open(&recording).await.expect("recording");
+
+    let mut client = SignalrClient::from_transport("Streaming", transport);
+    let initial = signalr::subscribe(&mut client, &[])
+        .await
+        .expect("initial state");
+    assert_eq!(initial, race);
+
+    let replayed = timeout(
+        Duration::from_secs(5),
+        signalr::listen_raw(client).collect::<Vec<String>>(),
+    )
+    .await
+    .expect("recording ends");
+
+    assert_eq!(
+        replayed,
+        [
+            frames[0].clone(),
+            frames[1].clone(),
+            json!({ "R": race }).to_string(),
+        ]
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
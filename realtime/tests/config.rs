@@ -0,0 +1,134 @@
+use std::{env, fs, sync::Mutex};
+
+use anyhow::Error;
+use realtime::config::Config;
+
+/// The env is shared by the whole process, tests setting it take turns.
+static ENV: Mutex<()> = Mutex::new(());
+
+/// Env vars set by these tests, cleared before each.
+const VARS: [&str; 6] = [
+    "CONFIG",
+    "ADDRESS",
+    "ORIGIN",
+    "BROADCAST_CAPACITY",
+    "RESTART_MAX_ATTEMPTS",
+    "F1_PROTOCOL",
+];
+
+/// Load the config from `toml` with `vars` set.
+fn load(name: &str, toml: &str, vars: &[(&str, &str)]) -> Result<Config, Error> {
+    let _guard = ENV.lock().unwrap_or_else(|err| err.into_inner());
+
+    let path = env::temp_dir().join(format!(
+        "realtime-config-{name}-{}.toml",
+        std::process::id()
+    ));
+    fs::write(&path, toml).expect("config written");
+
+    // SAFETY: the lock keeps the tests of this binary from touching the env at the same time
+    unsafe {
+        for var in VARS {
+            env::remove_var(var);
+        }
+
+        env::set_var("CONFIG", &path);
+
+        for (var, value) in vars {
+            env::set_var(var, value);
+        }
+    }
+
+    let config = Config::load();
+
+    let _ = fs::remove_file(&path);
+
+    config
+}
+
+fn error(result: Result<Config, Error>) -> String {
+    format!("{:#}", result.expect_err("config rejected"))
+}
+
+#[test]
+fn env_overrides_the_toml_file() {
+    let toml = r#"
+        address = "127.0.0.1:4000"
+        broadcast_capacity = 32
+
+        [restart]
+        max_attempts = 3
+
+        [upstream]
+        protocol = "core"
+    "#;
+
+    let config = load(
+        "layering",
+        toml,
+        &[("ADDRESS", "0.0.0.0:5000"), ("RESTART_MAX_ATTEMPTS", "5")],
+    )
+    .expect("valid config");
+
+    assert_eq!(config.address, "0.0.0.0:5000");
+    assert_eq!(config.restart.max_attempts, Some(5));
+
+    // not overridden
+    assert_eq!(config.broadcast_capacity, 32);
+    assert_eq!(config.upstream.protocol, "core");
+    assert_eq!(config.upstream.url(), "livetiming.formula1.com/signalrcore");
+
+    // neither in the file nor the env
+    assert_eq!(config.keep_alive.interval, 15);
+    assert_eq!(config.origins, ["https://f1-dash.com"]);
+}
+
+#[test]
+fn trims_origins_and_drops_empty_ones() {
+    let config = load(
+        "origins",
+        "",
+        &[("ORIGIN", " https://f1-dash.com ; ;http://localhost:3000;")],
+    )
+    .expect("valid config");
+
+    assert_eq!(
+        config.origins,
+        ["https://f1-dash.com", "http://localhost:3000"]
+    );
+}
+
+#[test]
+fn rejects_invalid_env_values() {
+    let err = error(load("capacity", "", &[("BROADCAST_CAPACITY", "many")]));
+    assert!(err.contains("BROADCAST_CAPACITY"), "{err}");
+
+    let err = error(load("attempts", "", &[("RESTART_MAX_ATTEMPTS", "-1")]));
+    assert!(err.contains("RESTART_MAX_ATTEMPTS"), "{err}");
+
+    let err = error(load("protocol", "", &[("F1_PROTOCOL", "websocket")]));
+    assert!(err.contains("upstream.protocol"), "{err}");
+
+    let err = error(load("no-origins", "", &[("ORIGIN", " ; ")]));
+    assert!(err.contains("origins must not be empty"), "{err}");
+}
+
+#[test]
+fn rejects_invalid_toml_values() {
+    let err = error(load("zero-capacity", "broadcast_capacity = 0", &[]));
+    assert!(err.contains("broadcast_capacity"), "{err}");
+
+    let err = error(load("address", r#"address = "localhost""#, &[]));
+    assert!(err.contains("host:port"), "{err}");
+
+    let err = error(load("unknown", "[upstream]\nprotcol = \"core\"", &[]));
+    assert!(err.contains("protcol"), "{err}");
+
+    // the env is applied before validating, it can't fix an invalid file
+    let err = error(load(
+        "delays",
+        "[restart]\ndelay = 30\nmax_delay = 10",
+        &[("ADDRESS", "0.0.0.0:5000")],
+    ));
+    assert!(err.contains("restart.max_delay"), "{err}");
+}
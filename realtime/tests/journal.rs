@@ -0,0 +1,167 @@
+use std::{sync::Arc, time::Duration};
+
+use serde_json::{Value, json};
+use tokio::time::{Instant, advance};
+
+use realtime::{
+    f1::TIMESTAMP_KEY,
+    services::journal::{Entry, EntryKind, Journal, Next},
+};
+
+const RETENTION: Duration = Duration::from_secs(60);
+
+fn update(topic: &str, data: Value) -> Arc<str> {
+    json!({ topic: data, TIMESTAMP_KEY: "2024-05-01T12:00:00.000Z" })
+        .to_string()
+        .into()
+}
+
+fn seqs(entries: &[Entry]) -> Vec<u64> {
+    entries.iter().map(|entry| entry.seq).collect()
+}
+
+#[tokio::test]
+async fn numbers_entries_in_order() {
+    let journal = Journal::new(RETENTION);
+
+    let initial = journal.reset(json!({})).await;
+    let first = journal.push(update("Heartbeat", json!({}))).await;
+    let session = journal.session(json!({})).await;
+
+    assert_eq!(first, initial + 1);
+    assert_eq!(session, first + 1);
+    assert_eq!(journal.latest(), session);
+}
+
+#[tokio::test]
+async fn since_returns_the_entries_after_a_sequence_number() {
+    let journal = Journal::new(RETENTION);
+
+    let a = journal.push(update("Heartbeat", json!({ "n": 1 }))).await;
+    let b = journal.push(update("Heartbeat", json!({ "n": 2 }))).await;
+    let c = journal.push(update("Heartbeat", json!({ "n": 3 }))).await;
+
+    assert_eq!(seqs(&journal.since(a).await.expect("retained")), [b, c]);
+    assert_eq!(
+        seqs(&journal.since(a - 1).await.expect("retained")),
+        [a, b, c]
+    );
+    assert_eq!(journal.since(c).await.map(|entries| entries.len()), Some(0));
+
+    // never handed out
+    assert!(journal.since(c + 1).await.is_none());
+    assert!(journal.since(a - 2).await.is_none());
+}
+
+#[tokio::test]
+async fn next_returns_the_following_entry() {
+    let journal = Journal::new(RETENTION);
+
+    let a = journal.push(update("Heartbeat", json!({ "n": 1 }))).await;
+    let b = journal.push(update("Heartbeat", json!({ "n": 2 }))).await;
+
+    assert!(matches!(journal.next(a).await, Next::Entry(entry) if entry.seq == b));
+    assert!(matches!(journal.next(b).await, Next::Pending));
+    assert!(matches!(journal.next(b + 1).await, Next::Evicted));
+}
+
+#[tokio::test]
+async fn unknown_sequence_numbers_dont_overflow() {
+    let journal = Journal::new(RETENTION);
+    journal.push(update("Heartbeat", json!({}))).await;
+
+    assert!(journal.since(u64::MAX).await.is_none());
+    assert!(matches!(journal.next(u64::MAX).await, Next::Evicted));
+    assert!(journal.since(0).await.is_none());
+    assert!(matches!(journal.next(0).await, Next::Evicted));
+}
+
+#[tokio::test(start_paused = true)]
+async fn evicts_entries_past_the_retention() {
+    let journal = Journal::new(RETENTION);
+
+    let a = journal.reset(json!({ "Heartbeat": { "n": 0 } })).await;
+    let b = journal.push(update("Heartbeat", json!({ "n": 1 }))).await;
+
+    advance(RETENTION + Duration::from_secs(1)).await;
+
+    let c = journal.push(update("Heartbeat", json!({ "n": 2 }))).await;
+
+    assert!(journal.since(a).await.is_none());
+    assert!(matches!(journal.next(a).await, Next::Evicted));
+    assert_eq!(seqs(&journal.since(b).await.expect("retained")), [c]);
+    assert!(matches!(journal.next(b).await, Next::Entry(entry) if entry.seq == c));
+
+    // evicted entries are part of the state the journal starts from
+    let (state, seq) = journal.snapshot_at(Instant::now() - RETENTION).await;
+    assert_eq!(state, json!({ "Heartbeat": { "n": 1 } }));
+    assert_eq!(seq, b);
+}
+
+#[tokio::test(start_paused = true)]
+async fn snapshots_the_state_at_a_point_in_time() {
+    let journal = Journal::new(RETENTION);
+
+    let start = Instant::now();
+    let initial = journal
+        .reset(json!({ "Lines": { "1": { "Position": "1" } } }))
+        .await;
+
+    advance(Duration::from_secs(5)).await;
+    let moved = journal
+        .push(update("Lines", json!({ "1": { "Position": "2" } })))
+        .await;
+
+    advance(Duration::from_secs(5)).await;
+    journal
+        .push(update("Lines", json!({ "44": { "Position": "1" } })))
+        .await;
+
+    let (state, seq) = journal.snapshot_at(start).await;
+    assert_eq!(state, json!({ "Lines": { "1": { "Position": "1" } } }));
+    assert_eq!(seq, initial);
+
+    let (state, seq) = journal.snapshot_at(start + Duration::from_secs(7)).await;
+    assert_eq!(state, json!({ "Lines": { "1": { "Position": "2" } } }));
+    assert_eq!(seq, moved);
+
+    // timestamps travel with the updates but are no part of the state
+    let (state, seq) = journal.snapshot_at(Instant::now()).await;
+    assert_eq!(
+        state,
+        json!({ "Lines": { "1": { "Position": "2" }, "44": { "Position": "1" } } })
+    );
+    assert_eq!(seq, journal.latest());
+}
+
+#[tokio::test(start_paused = true)]
+async fn sessions_replace_the_state() {
+    let journal = Journal::new(RETENTION);
+
+    journal.reset(json!({ "SessionInfo": { "Key": 1 } })).await;
+    journal.push(update("Heartbeat", json!({}))).await;
+    let session = journal
+        .session(json!({ "SessionInfo": { "Key": 2 } }))
+        .await;
+
+    let (state, seq) = journal.snapshot_at(Instant::now()).await;
+
+    assert_eq!(state, json!({ "SessionInfo": { "Key": 2 } }));
+    assert_eq!(seq, session);
+
+    let entries = journal.since(session - 1).await.expect("retained");
+    assert!(
+        matches!(&entries[0].kind, EntryKind::Session(state) if state["SessionInfo"]["Key"] == 2)
+    );
+}
+
+#[tokio::test]
+async fn empty_journal_snapshots_nothing() {
+    let journal = Journal::new(RETENTION);
+
+    let (state, seq) = journal.snapshot_at(Instant::now()).await;
+
+    assert_eq!(state, json!({}));
+    assert_eq!(seq, journal.latest());
+    assert!(matches!(journal.next(seq).await, Next::Pending));
+}
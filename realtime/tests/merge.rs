@@ -0,0 +1,258 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Value, json};
+
+use realtime::services::state_service::{MAX_ARRAY_GAP, merge};
+
+/// Marks full states in the feed, not part of the data.
+const KEYFRAME_KEY: &str = "_kf";
+
+fn merged(mut base: Value, update: Value) -> Value {
+    merge(&mut base, update);
+    base
+}
+
+#[test]
+fn merges_objects_recursively() {
+    let state = json!({ "A": { "B": 1, "C": { "D": 2 } } });
+    let update = json!({ "A": { "C": { "E": 3 } } });
+
+    assert_eq!(
+        merged(state, update),
+        json!({ "A": { "B": 1, "C": { "D": 2, "E": 3 } } })
+    );
+}
+
+#[test]
+fn updates_arrays_by_index() {
+    let state = json!({ "Stints": [{ "Laps": 1 }, { "Laps": 2 }] });
+    let update = json!({ "Stints": { "1": { "Laps": 3 }, "2": { "Laps": 0 } } });
+
+    assert_eq!(
+        merged(state, update),
+        json!({ "Stints": [{ "Laps": 1 }, { "Laps": 3 }, { "Laps": 0 }] })
+    );
+}
+
+#[test]
+fn pads_sparse_array_indices() {
+    let state = json!({ "Stints": [{ "Laps": 1 }] });
+    let update = json!({ "Stints": { "3": { "Laps": 0 } } });
+
+    assert_eq!(
+        merged(state, update),
+        json!({ "Stints": [{ "Laps": 1 }, null, null, { "Laps": 0 }] })
+    );
+}
+
+#[test]
+fn ignores_indices_far_past_the_end() {
+    let state = json!({ "Stints": [{ "Laps": 1 }] });
+    let update = json!({ "Stints": {
+        "4294967295": { "Laps": 2 },
+        "18446744073709551615": { "Laps": 3 },
+        "1": { "Laps": 4 }
+    } });
+
+    assert_eq!(
+        merged(state, update),
+        json!({ "Stints": [{ "Laps": 1 }, { "Laps": 4 }] })
+    );
+}
+
+#[test]
+fn extends_arrays_by_at_most_the_gap() {
+    let mut state = json!([0]);
+
+    merge(&mut state, json!({ (MAX_ARRAY_GAP + 1).to_string(): 1 }));
+    assert_eq!(state, json!([0]));
+
+    merge(&mut state, json!({ MAX_ARRAY_GAP.to_string(): 1 }));
+    assert_eq!(state.as_array().map(Vec::len), Some(MAX_ARRAY_GAP + 1));
+}
+
+#[test]
+fn deletes_object_keys() {
+    let state = json!({ "Lines": { "1": { "Tla": "VER" }, "44": { "Tla": "HAM" } } });
+    let update = json!({ "Lines": { "_deleted": ["44"] } });
+
+    assert_eq!(
+        merged(state, update),
+        json!({ "Lines": { "1": { "Tla": "VER" } } })
+    );
+}
+
+#[test]
+fn leaves_arrays_alone_on_deletion_markers() {
+    let state = json!({ "Stints": ["a", "b", "c", "d"] });
+    let update = json!({ "Stints": { "_deleted": [1, "3"], "0": "e" } });
+
+    // applying it twice doesn't take more elements away
+    let once = merged(state, update.clone());
+    assert_eq!(once, json!({ "Stints": ["e", "b", "c", "d"] }));
+    assert_eq!(merged(once.clone(), update), once);
+}
+
+#[test]
+fn deletes_before_applying_the_rest() {
+    let state = json!({ "Lines": { "1": { "Tla": "VER", "Old": true } } });
+    let update = json!({ "Lines": { "_deleted": ["1"], "1": { "Tla": "VER" } } });
+
+    assert_eq!(
+        merged(state, update),
+        json!({ "Lines": { "1": { "Tla": "VER" } } })
+    );
+}
+
+#[test]
+fn strips_markers_from_new_objects() {
+    let state = json!({});
+    let update = json!({ "Lines": { "1": { "_deleted": ["Old"], "Tla": "VER" } } });
+
+    assert_eq!(
+        merged(state, update),
+        json!({ "Lines": { "1": { "Tla": "VER" } } })
+    );
+}
+
+#[test]
+fn replaces_other_values() {
+    let state = json!({ "A": [1, 2, 3], "B": { "C": 1 }, "D": "x" });
+    let update = json!({ "A": [4], "B": null, "D": 5 });
+
+    assert_eq!(
+        merged(state, update),
+        json!({ "A": [4], "B": null, "D": 5 })
+    );
+}
+
+/// Replays the hand written recording in `tests/recordings`. Its snapshots were written
+/// against this merge, so it only guards against regressions.
+#[test]
+fn synthetic_recording_replays() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/recordings/synthetic.txt");
+    let recording = fs::read_to_string(&path).expect("readable recording");
+
+    assert_eq!(check_recording(&recording), Ok(2));
+}
+
+/// Replays every recording made with `simulator save` in `RECORDINGS_DIR` the way realtime
+/// ingests it, and compares the merged state against each upstream state snapshot.
+#[test]
+#[ignore = "needs real session recordings in RECORDINGS_DIR"]
+fn recordings_match_upstream_snapshots() {
+    let dir = PathBuf::from(env::var_os("RECORDINGS_DIR").expect("RECORDINGS_DIR set"));
+
+    let mut checked = 0;
+    let mut failures = Vec::new();
+
+    for entry in fs::read_dir(&dir).expect("recordings directory") {
+        let path = entry.expect("recording").path();
+
+        if path.extension().is_none_or(|ext| ext != "txt") {
+            continue;
+        }
+
+        let recording = fs::read_to_string(&path).expect("readable recording");
+
+        match check_recording(&recording) {
+            Ok(snapshots) => checked += snapshots,
+            Err(err) => failures.push(format!("{}: {err}", path.display())),
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+    assert!(checked > 0, "no snapshots found in {}", dir.display());
+}
+
+/// Number of snapshots compared, or where the first one differed.
+fn check_recording(recording: &str) -> Result<usize, String> {
+    let mut lines = recording.lines().enumerate();
+
+    let (_, initial) = lines.next().ok_or("empty recording")?;
+    let mut state: Value = serde_json::from_str(initial).map_err(|err| err.to_string())?;
+
+    let mut checked = 0;
+
+    for (number, line) in lines {
+        let Ok(mut frame) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+
+        if let Some(snapshot) = frame.get_mut("R").map(Value::take) {
+            // without an id it's the state after a resubscribe, updates may be missing before it
+            if frame.get("I").is_none() {
+                state = snapshot;
+                continue;
+            }
+
+            let Value::Object(topics) = snapshot else {
+                continue;
+            };
+
+            for (topic, expected) in topics {
+                let actual = state.get(&topic).cloned().unwrap_or(Value::Null);
+
+                if let Some(path) = difference(&strip(actual), &strip(expected), &topic) {
+                    return Err(format!("line {}: differs at {path}", number + 1));
+                }
+            }
+
+            checked += 1;
+            continue;
+        }
+
+        let Some(messages) = frame.get("M").and_then(Value::as_array) else {
+            continue;
+        };
+
+        for message in messages {
+            if let Some([topic, data, ..]) = message
+                .get("A")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                && let Some(topic) = topic.as_str()
+            {
+                merge(&mut state, json!({ topic: data }));
+            }
+        }
+    }
+
+    Ok(checked)
+}
+
+fn strip(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(key, _)| key != KEYFRAME_KEY)
+                .map(|(key, value)| (key, strip(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(strip).collect()),
+        value => value,
+    }
+}
+
+/// Path of the first difference between both values.
+fn difference(actual: &Value, expected: &Value, path: &str) -> Option<String> {
+    match (actual, expected) {
+        (Value::Object(a), Value::Object(b)) => a.keys().chain(b.keys()).find_map(|key| {
+            let path = format!("{path}/{key}");
+            match (a.get(key), b.get(key)) {
+                (Some(a), Some(b)) => difference(a, b, &path),
+                _ => Some(path),
+            }
+        }),
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => a
+            .iter()
+            .zip(b)
+            .enumerate()
+            .find_map(|(index, (a, b))| difference(a, b, &format!("{path}/{index}"))),
+        (a, b) if a == b => None,
+        _ => Some(path.to_string()),
+    }
+}
@@ -0,0 +1,81 @@
+use serde_json::json;
+
+use realtime::services::session::Session;
+
+#[test]
+fn identifies_sessions_by_path() {
+    let info = json!({
+        "Path": "2024/2024-03-02_Bahrain_Grand_Prix/2024-03-02_Race/",
+        "Key": 9472,
+        "Name": "Race",
+        "StartDate": "2024-03-02T18:00:00",
+        "Meeting": { "Key": 1229, "Name": "Bahrain Grand Prix" }
+    });
+
+    let session = Session::from_info(&info).expect("session");
+
+    assert_eq!(
+        session,
+        Session {
+            id: "2024/2024-03-02_Bahrain_Grand_Prix/2024-03-02_Race/".to_string(),
+            meeting: Some("Bahrain Grand Prix".to_string()),
+            name: Some("Race".to_string()),
+            date: Some("2024-03-02".to_string()),
+        }
+    );
+}
+
+#[test]
+fn falls_back_to_meeting_and_session_keys() {
+    let info = json!({ "Path": "", "Key": 9472, "Meeting": { "Key": 1229 } });
+
+    assert_eq!(Session::from_info(&info).expect("session").id, "1229/9472");
+
+    let info = json!({ "Key": "9472" });
+
+    assert_eq!(
+        Session::from_info(&info).expect("session").id,
+        "null/\"9472\""
+    );
+}
+
+#[test]
+fn partial_updates_dont_identify_a_session() {
+    assert_eq!(
+        Session::from_info(&json!({ "ArchiveStatus": { "Status": "Complete" } })),
+        None
+    );
+    assert_eq!(Session::from_info(&json!({ "Key": null })), None);
+    assert_eq!(Session::from_state(&json!({ "Heartbeat": {} })), None);
+}
+
+#[test]
+fn reads_the_session_of_a_state() {
+    let state = json!({ "SessionInfo": { "Key": 1, "Meeting": { "Key": 2 } } });
+
+    assert_eq!(Session::from_state(&state).expect("session").id, "2/1");
+}
+
+#[test]
+fn names_files_after_date_meeting_and_session() {
+    let info = json!({
+        "Path": "2024/2024-05-26_Monaco_Grand_Prix/2024-05-25_Qualifying/",
+        "Name": "Qualifying",
+        "StartDate": "2024-05-25T16:00:00",
+        "Meeting": { "Name": "Grand Prix de Monaco – Monte-Carlo" }
+    });
+
+    let session = Session::from_info(&info).expect("session");
+
+    assert_eq!(
+        session.file_stem(),
+        "2024-05-25_grand-prix-de-monaco-monte-carlo_qualifying"
+    );
+}
+
+#[test]
+fn names_files_after_the_id_without_details() {
+    let session = Session::from_info(&json!({ "Path": "2024/Test/Day_1/" })).expect("session");
+
+    assert_eq!(session.file_stem(), "2024-test-day-1");
+}
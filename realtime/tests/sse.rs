@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use realtime::relay::sse::{SseEvent, SseParser};
+
+fn event(event: &str, id: &str, data: &[&str]) -> SseEvent {
+    SseEvent {
+        event: Some(event.to_string()),
+        id: Some(id.to_string()),
+        data: data.iter().map(|line| line.to_string()).collect(),
+        retry: None,
+    }
+}
+
+#[test]
+fn parses_events_split_across_chunks() {
+    let mut parser = SseParser::default();
+
+    assert!(parser.push(b"event: update\nid: 4").is_empty());
+    assert!(parser.push(b"2\ndata: {\"Heart").is_empty());
+
+    let events = parser.push(b"beat\":{}}\n\nevent: initial\n");
+
+    assert_eq!(events, [event("update", "42", &[r#"{"Heartbeat":{}}"#])]);
+    assert_eq!(
+        parser.push(b"id: 43\ndata: {}\n\n"),
+        [event("initial", "43", &["{}"])]
+    );
+}
+
+#[test]
+fn joins_data_lines() {
+    let mut parser = SseParser::default();
+
+    let events = parser.push(b"event: initial\nid: 1\ndata: {\"A\":\ndata: 1}\n\n");
+
+    assert_eq!(events[0].data(), "{\"A\":\n1}");
+}
+
+#[test]
+fn accepts_crlf_and_values_without_space() {
+    let mut parser = SseParser::default();
+
+    let events = parser.push(b"event:update\r\nid:7\r\ndata:{}\r\n\r\n");
+
+    assert_eq!(events, [event("update", "7", &["{}"])]);
+}
+
+#[test]
+fn skips_keep_alive_comments() {
+    let mut parser = SseParser::default();
+
+    assert!(parser.push(b": keep-alive-text\n\n").is_empty());
+    assert!(parser.push(b":\n\n\n").is_empty());
+}
+
+#[test]
+fn reads_the_reconnect_hint() {
+    let mut parser = SseParser::default();
+
+    let events = parser.push(b"event: reconnect\nretry: 2000\ndata: \n\n");
+
+    assert_eq!(events[0].event.as_deref(), Some("reconnect"));
+    assert_eq!(events[0].retry, Some(Duration::from_secs(2)));
+}
+
+#[test]
+fn ignores_unknown_fields_and_invalid_retries() {
+    let mut parser = SseParser::default();
+
+    let events = parser.push(b"event: update\nfoo: bar\nretry: soon\ndata: {}\n\n");
+
+    assert_eq!(events[0].retry, None);
+    assert_eq!(events[0].data, ["{}"]);
+}
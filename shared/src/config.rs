@@ -0,0 +1,211 @@
+use std::{collections::HashSet, env, fmt::Display, fs, str::FromStr, time::Duration};
+
+use anyhow::{anyhow, Context, Error};
+use serde::{de::DeserializeOwned, Deserialize};
+
+/// Topics subscribed on the f1 feed unless configured otherwise.
+pub const TOPICS: [&str; 17] = [
+    "Heartbeat",
+    "CarData.z",
+    "Position.z",
+    "ExtrapolatedClock",
+    "TimingStats",
+    "TimingAppData",
+    "WeatherData",
+    "TrackStatus",
+    "SessionStatus",
+    "DriverList",
+    "RaceControlMessages",
+    "SessionInfo",
+    "SessionData",
+    "LapCount",
+    "TimingData",
+    "TeamRadio",
+    "ChampionshipPrediction",
+];
+
+const URL: &str = "livetiming.formula1.com/signalr";
+const CORE_URL: &str = "livetiming.formula1.com/signalrcore";
+
+/// Names of the signalr protocols, parsed by the crates that connect.
+const PROTOCOLS: [&str; 2] = ["classic", "core"];
+
+/// Where and how to connect to the f1 feed, the `[upstream]` table
+/// shared by realtime and `simulator save`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpstreamConfig {
+    /// `F1_URL`, depends on the protocol by default.
+    pub url: Option<String>,
+    /// `F1_HUB`
+    pub hub: String,
+    /// `F1_PROTOCOL`, "classic" or "core".
+    pub protocol: String,
+    /// Full websocket url of a simulator, `F1_DEV_URL`.
+    pub dev_url: Option<String>,
+    /// `TOPICS` separated by `,`.
+    pub topics: Vec<String>,
+    /// Seconds without any update, not even a `Heartbeat`, before reconnecting,
+    /// never when 0, `F1_IDLE_TIMEOUT`.
+    pub idle_timeout: u64,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            hub: "Streaming".to_string(),
+            protocol: "classic".to_string(),
+            dev_url: None,
+            topics: TOPICS.iter().map(|topic| topic.to_string()).collect(),
+            idle_timeout: 300,
+        }
+    }
+}
+
+impl UpstreamConfig {
+    pub fn env_overrides(&mut self) -> Result<(), Error> {
+        if let Ok(url) = env::var("F1_URL") {
+            self.url = Some(url);
+        }
+
+        if let Ok(dev_url) = env::var("F1_DEV_URL") {
+            self.dev_url = Some(dev_url);
+        }
+
+        env_override("F1_HUB", &mut self.hub)?;
+        env_override("F1_PROTOCOL", &mut self.protocol)?;
+
+        if let Some(topics) = env_list("TOPICS", ',') {
+            self.topics = topics;
+        }
+
+        env_override("F1_IDLE_TIMEOUT", &mut self.idle_timeout)?;
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(url) = &self.url {
+            if url.is_empty() || url.contains("://") {
+                return Err(anyhow!(
+                    "upstream.url {url:?} must be a host and path without a scheme"
+                ));
+            }
+        }
+
+        if !PROTOCOLS.contains(&self.protocol.as_str()) {
+            return Err(anyhow!(
+                "upstream.protocol {:?} must be one of {PROTOCOLS:?}",
+                self.protocol
+            ));
+        }
+
+        if self.hub.is_empty() {
+            return Err(anyhow!("upstream.hub must not be empty"));
+        }
+
+        validate_topics(&self.topics).context("upstream.topics")
+    }
+
+    pub fn url(&self) -> &str {
+        match &self.url {
+            Some(url) => url,
+            None if self.protocol == "core" => CORE_URL,
+            None => URL,
+        }
+    }
+
+    pub fn topics(&self) -> Vec<&str> {
+        self.topics.iter().map(String::as_str).collect()
+    }
+
+    /// Quiet periods longer than this end the connection, and the reconnect subscribes
+    /// again for the full state.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_timeout > 0).then(|| Duration::from_secs(self.idle_timeout))
+    }
+}
+
+/// Read the TOML file at `CONFIG`, or the defaults when it's not set.
+/// Missing tables and keys keep their defaults.
+pub fn load_config<T: DeserializeOwned + Default>() -> Result<T, Error> {
+    let Ok(path) = env::var("CONFIG") else {
+        return Ok(T::default());
+    };
+
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read config {path}"))?;
+
+    toml::from_str(&content).with_context(|| format!("invalid config {path}"))
+}
+
+/// Replace `value` with the env var `name` when it's set.
+pub fn env_override<T>(name: &str, value: &mut T) -> Result<(), Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(var) = env::var(name) {
+        *value = parse_var(name, &var)?;
+    }
+
+    Ok(())
+}
+
+/// Set the optional `value` to the env var `name` when it's set.
+pub fn env_override_some<T>(name: &str, value: &mut Option<T>) -> Result<(), Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(var) = env::var(name) {
+        *value = Some(parse_var(name, &var)?);
+    }
+
+    Ok(())
+}
+
+fn parse_var<T>(name: &str, var: &str) -> Result<T, Error>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    var.parse()
+        .map_err(|err| anyhow!("invalid {name} {var:?}: {err}"))
+}
+
+/// A list from the env var `name` split on `separator`, without blank entries.
+/// `None` when it's not set.
+pub fn env_list(name: &str, separator: char) -> Option<Vec<String>> {
+    let var = env::var(name).ok()?;
+
+    Some(
+        var.split(separator)
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Topics must not be empty or repeated.
+fn validate_topics(topics: &[String]) -> Result<(), Error> {
+    if topics.is_empty() {
+        return Err(anyhow!("topics must not be empty"));
+    }
+
+    let mut seen = HashSet::new();
+
+    for topic in topics {
+        if topic.trim().is_empty() {
+            return Err(anyhow!("topics must not contain empty names"));
+        }
+
+        if !seen.insert(topic) {
+            return Err(anyhow!("topic {topic:?} is listed more than once"));
+        }
+    }
+
+    Ok(())
+}
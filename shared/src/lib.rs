@@ -1,4 +1,7 @@
 // pub mod events;
 // pub mod models;
+pub mod config;
 mod log;
+mod shutdown;
 pub use log::tracing_subscriber;
+pub use shutdown::{shutdown_deadline, shutdown_signal};
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use anyhow::Error;
+use tokio::signal;
+
+use crate::config::env_override;
+
+/// Resolves once the process receives SIGTERM or SIGINT (ctrl-c).
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// How long to drain after the shutdown signal before exiting anyway,
+/// `SHUTDOWN_DEADLINE` in seconds, 20 by default. Read at startup, so a wrong value
+/// fails right away and not once the server is shutting down.
+pub fn shutdown_deadline() -> Result<Duration, Error> {
+    let mut seconds: u64 = 20;
+    env_override("SHUTDOWN_DEADLINE", &mut seconds)?;
+
+    Ok(Duration::from_secs(seconds))
+}
@@ -0,0 +1,485 @@
+use std::{str::FromStr, time::Duration};
+
+use reqwest::{
+    Url,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::Request};
+use tracing::{debug, info};
+
+use crate::{
+    CoreTransport, ReconnectPolicy, ReconnectingClient, SignalrClient, SignalrError, Transport,
+    WebSocketTransport, WsStream, invoke::Options, proxy, resume::CursorTracker,
+    resume::ResumeHandle,
+};
+
+#[derive(Serialize)]
+struct Connection {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct ConnectionData([Connection; 1]);
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct NegotiationResponse {
+    pub connection_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CoreNegotiationResponse {
+    connection_id: String,
+    /// Only sent from negotiate version 1 on, older servers use the connection id.
+    connection_token: Option<String>,
+    /// Set when the server redirects to another service, like Azure SignalR.
+    url: Option<String>,
+}
+
+/// The wire protocol spoken with the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// Classic ASP.NET SignalR, `/negotiate`, `/connect` and `/reconnect` with `clientProtocol` 1.5.
+    #[default]
+    Classic,
+    /// ASP.NET Core SignalR with the JSON hub protocol. The url points at the hub itself,
+    /// like `livetiming.formula1.com/signalrcore`, and connections can't be resumed.
+    Core,
+}
+
+impl FromStr for Protocol {
+    type Err = SignalrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "classic" => Ok(Protocol::Classic),
+            "core" => Ok(Protocol::Core),
+            other => Err(SignalrError::Protocol(format!(
+                "unknown protocol {other}, expected classic or core"
+            ))),
+        }
+    }
+}
+
+/// From the same names as [`FromStr`], so configs are checked when they're read.
+impl<'de> Deserialize<'de> for Protocol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Negotiation {
+    pub(crate) token: String,
+    pub(crate) cookie: String,
+    pub(crate) connection_data: String,
+}
+
+/// Configures how a [`SignalrClient`] connects, [`crate::create_client`] uses the defaults.
+///
+/// ```ignore
+/// let client = ClientBuilder::new("localhost:5000/signalr", "Streaming")
+///     .secure(false)
+///     .bearer_token("token")
+///     .connect_timeout(Duration::from_secs(10))
+///     .connect()
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    url: String,
+    hub: String,
+    secure: bool,
+    endpoint: Option<String>,
+    headers: Vec<(String, String)>,
+    bearer_token: Option<String>,
+    user_agent: String,
+    proxy: Option<String>,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    protocol: Protocol,
+    protocol_version: String,
+}
+
+impl ClientBuilder {
+    /// `url` is the SignalR base without a scheme, like `livetiming.formula1.com/signalr`.
+    pub fn new(url: &str, hub: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            hub: hub.to_string(),
+            secure: true,
+            endpoint: None,
+            headers: Vec::new(),
+            bearer_token: None,
+            user_agent: "BestHTTP".to_string(),
+            proxy: None,
+            connect_timeout: None,
+            read_timeout: None,
+            idle_timeout: None,
+            protocol: Protocol::Classic,
+            protocol_version: "1.5".to_string(),
+        }
+    }
+
+    /// Use `https`/`wss` (the default) or plain `http`/`ws`, for local stand-ins.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Connect straight to this WebSocket url and skip negotiation, like the
+    /// simulator's replay server. Such connections can't be resumed, and since
+    /// they replay recorded responses their ids aren't checked.
+    pub fn endpoint(mut self, endpoint: Option<String>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    /// Extra header sent with negotiation and the WebSocket handshake.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Send an `Authorization: Bearer` header.
+    pub fn bearer_token(mut self, token: &str) -> Self {
+        self.bearer_token = Some(token.to_string());
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    /// Proxy for all requests, `http://`, `socks5://` or `socks5h://`, with optional credentials.
+    pub fn proxy(mut self, proxy: &str) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    /// Limit for negotiation and establishing the WebSocket connection.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Limit for waiting on a single response or frame.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// End the connection with [`SignalrError::Idle`] when no data arrives within `timeout`,
+    /// keep-alive frames don't count. Unlike the read timeout this catches a stalled feed
+    /// on a socket that is still alive. Pick it well above the `Heartbeat` interval.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Speak classic SignalR (the default) or ASP.NET Core SignalR. Either way the client
+    /// yields the same frames, updates and events.
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// The `clientProtocol` sent to the server, `1.5` by default.
+    pub fn client_protocol(mut self, version: &str) -> Self {
+        self.protocol_version = version.to_string();
+        self
+    }
+
+    pub fn hub(&self) -> &str {
+        &self.hub
+    }
+
+    pub fn protocol_version(&self) -> &str {
+        &self.protocol_version
+    }
+
+    /// A client that reconnects with this configuration, see [`crate::reconnecting`].
+    pub fn reconnecting(self, topics: &[&str], policy: ReconnectPolicy) -> ReconnectingClient {
+        ReconnectingClient::new(self, topics, policy)
+    }
+
+    /// Client for an already established connection, like [`SignalrClient::from_transport`]
+    /// but with the idle timeout of this configuration.
+    pub fn from_transport(&self, transport: impl Transport) -> SignalrClient {
+        SignalrClient::spawn(
+            &self.hub,
+            transport,
+            CursorTracker::default(),
+            None,
+            self.options(),
+        )
+    }
+
+    pub async fn connect(&self) -> Result<SignalrClient, SignalrError> {
+        if let Some(endpoint) = &self.endpoint {
+            let url =
+                Url::parse(endpoint).map_err(|err| SignalrError::InvalidUrl(err.to_string()))?;
+
+            let stream = self.open(url, None).await?;
+
+            let options = Options {
+                check_response_ids: false,
+                ..self.options()
+            };
+
+            return self.spawn_unresumable(stream, options).await;
+        }
+
+        if self.protocol == Protocol::Core {
+            return self.connect_core().await;
+        }
+
+        let negotiation = self.negotiate().await?;
+
+        let url = self.url(
+            "connect",
+            &[
+                ("clientProtocol", &self.protocol_version),
+                ("transport", "webSockets"),
+                ("connectionToken", &negotiation.token),
+            ],
+        )?;
+
+        let stream = self.open(url, Some(&negotiation.cookie)).await?;
+
+        let resume = ResumeHandle {
+            builder: self.clone(),
+            negotiation,
+            cursor: CursorTracker::default(),
+        };
+
+        Ok(SignalrClient::resumable(self.transport(stream), resume))
+    }
+
+    async fn connect_core(&self) -> Result<SignalrClient, SignalrError> {
+        let scheme = if self.secure { "https" } else { "http" };
+
+        let url = Url::parse_with_params(
+            &format!("{scheme}://{}/negotiate", self.url),
+            &[("negotiateVersion", "1")],
+        )
+        .map_err(|err| SignalrError::InvalidUrl(err.to_string()))?;
+
+        let res = self.http()?.post(url).send().await?.error_for_status()?;
+
+        // unlike classic servers, core servers only need a cookie behind sticky load balancers
+        let cookie = res
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let res: CoreNegotiationResponse =
+            serde_json::from_str(&res.text().await?).map_err(SignalrError::InvalidToken)?;
+
+        if let Some(redirect) = res.url {
+            return Err(SignalrError::Protocol(format!(
+                "negotiate redirects to {redirect}, which is not supported"
+            )));
+        }
+
+        let token = res.connection_token.unwrap_or(res.connection_id);
+
+        let scheme = if self.secure { "wss" } else { "ws" };
+
+        let url = Url::parse_with_params(&format!("{scheme}://{}", self.url), &[("id", &token)])
+            .map_err(|err| SignalrError::InvalidUrl(err.to_string()))?;
+
+        let stream = self.open(url, cookie.as_deref()).await?;
+
+        self.spawn_unresumable(stream, self.options()).await
+    }
+
+    /// Client for a connection that was opened without the classic negotiation.
+    async fn spawn_unresumable(
+        &self,
+        stream: WsStream,
+        options: Options,
+    ) -> Result<SignalrClient, SignalrError> {
+        let transport = self.transport(stream);
+
+        let client = match self.protocol {
+            Protocol::Classic => SignalrClient::spawn(
+                &self.hub,
+                transport,
+                CursorTracker::default(),
+                None,
+                options,
+            ),
+            Protocol::Core => SignalrClient::spawn(
+                &self.hub,
+                CoreTransport::handshake(transport, &self.hub).await?,
+                CursorTracker::default(),
+                None,
+                options,
+            ),
+        };
+
+        Ok(client)
+    }
+
+    pub(crate) fn options(&self) -> Options {
+        Options {
+            check_response_ids: true,
+            idle_timeout: self.idle_timeout,
+        }
+    }
+
+    pub(crate) fn transport(&self, stream: WsStream) -> WebSocketTransport {
+        WebSocketTransport::new(stream).read_timeout(self.read_timeout)
+    }
+
+    pub(crate) fn url(&self, path: &str, params: &[(&str, &str)]) -> Result<Url, SignalrError> {
+        let scheme = if self.secure { "wss" } else { "ws" };
+
+        Url::parse_with_params(&format!("{scheme}://{}/{path}", self.url), params)
+            .map_err(|err| SignalrError::InvalidUrl(err.to_string()))
+    }
+
+    fn headers(&self) -> Result<HeaderMap, SignalrError> {
+        let mut headers = HeaderMap::new();
+
+        headers.insert(
+            header::USER_AGENT,
+            HeaderValue::from_str(&self.user_agent)
+                .map_err(|_| SignalrError::InvalidHeader(header::USER_AGENT.to_string()))?,
+        );
+
+        if let Some(token) = &self.bearer_token {
+            headers.insert(
+                header::AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}"))
+                    .map_err(|_| SignalrError::InvalidHeader(header::AUTHORIZATION.to_string()))?,
+            );
+        }
+
+        for (name, value) in &self.headers {
+            let invalid = || SignalrError::InvalidHeader(name.clone());
+
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid())?,
+                HeaderValue::from_str(value).map_err(|_| invalid())?,
+            );
+        }
+
+        Ok(headers)
+    }
+
+    fn http(&self) -> Result<reqwest::Client, SignalrError> {
+        let mut http = reqwest::Client::builder().default_headers(self.headers()?);
+
+        if let Some(proxy) = &self.proxy {
+            http = http.proxy(
+                reqwest::Proxy::all(proxy)
+                    .map_err(|err| SignalrError::InvalidUrl(err.to_string()))?,
+            );
+        }
+
+        if let Some(timeout) = self.connect_timeout {
+            http = http.connect_timeout(timeout);
+        }
+
+        if let Some(timeout) = self.read_timeout {
+            http = http.read_timeout(timeout);
+        }
+
+        Ok(http.build()?)
+    }
+
+    async fn negotiate(&self) -> Result<Negotiation, SignalrError> {
+        let hub = ConnectionData([Connection {
+            name: self.hub.clone(),
+        }]);
+
+        let hub_param = serde_json::to_string(&hub)?;
+
+        let scheme = if self.secure { "https" } else { "http" };
+
+        let url = Url::parse_with_params(
+            &format!("{scheme}://{}/negotiate", self.url),
+            &[
+                ("clientProtocol", self.protocol_version.as_str()),
+                ("connectionData", &hub_param),
+            ],
+        )
+        .map_err(|err| SignalrError::InvalidUrl(err.to_string()))?;
+
+        let req = self.http()?.get(url).send().await?.error_for_status()?;
+
+        let cookie = req
+            .headers()
+            .get(header::SET_COOKIE)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(SignalrError::MissingCookie)?
+            .to_string();
+
+        let res: NegotiationResponse =
+            serde_json::from_str(&req.text().await?).map_err(SignalrError::InvalidToken)?;
+
+        Ok(Negotiation {
+            token: res.connection_token,
+            cookie,
+            connection_data: hub_param,
+        })
+    }
+
+    pub(crate) async fn open(
+        &self,
+        url: Url,
+        cookie: Option<&str>,
+    ) -> Result<WsStream, SignalrError> {
+        info!("connecting to {url}");
+
+        let mut req: Request<()> = url
+            .clone()
+            .into_client_request()
+            .map_err(|err| SignalrError::InvalidUrl(err.to_string()))?;
+
+        let headers = req.headers_mut();
+        headers.extend(self.headers()?);
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("gzip,identity"),
+        );
+
+        if let Some(cookie) = cookie {
+            headers.insert(
+                header::COOKIE,
+                cookie.parse().map_err(|_| SignalrError::MissingCookie)?,
+            );
+        }
+
+        let connect = async {
+            let (stream, res) = match &self.proxy {
+                Some(proxy) => {
+                    let tcp = proxy::connect(proxy, &url).await?;
+                    tokio_tungstenite::client_async_tls(req, tcp).await
+                }
+                None => tokio_tungstenite::connect_async(req).await,
+            }
+            .map_err(SignalrError::Handshake)?;
+
+            debug!(?res, "ws connected");
+
+            Ok(stream)
+        };
+
+        match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect)
+                .await
+                .map_err(|_| SignalrError::Timeout("connecting"))?,
+            None => connect.await,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::io::Read;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use flate2::read::DeflateDecoder;
+use futures::Stream;
+use serde_json::Value;
+use tokio_stream::StreamExt;
+use tracing::warn;
+
+use crate::{SignalrClient, SignalrError, UpdateArgs, listen};
+
+/// Suffix of topics whose data is sent as base64 encoded raw deflate, like `CarData.z`.
+pub const COMPRESSED_SUFFIX: &str = ".z";
+
+/// Inflate a base64 encoded raw deflate payload into JSON.
+pub fn inflate(data: &str) -> Result<Value, SignalrError> {
+    let bytes = BASE64_STANDARD
+        .decode(data)
+        .map_err(|err| SignalrError::Decode(err.to_string()))?;
+
+    let mut json = String::new();
+
+    DeflateDecoder::new(bytes.as_slice())
+        .read_to_string(&mut json)
+        .map_err(|err| SignalrError::Decode(err.to_string()))?;
+
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Decode all compressed topics of a state object, as returned by [`crate::subscribe`].
+/// Topics that fail to decode are left as they are.
+pub fn decode_state(state: Value) -> Value {
+    let Value::Object(topics) = state else {
+        return state;
+    };
+
+    let decoded = topics
+        .into_iter()
+        .map(|(topic, data)| {
+            let Some(name) = topic.strip_suffix(COMPRESSED_SUFFIX) else {
+                return (topic, data);
+            };
+
+            match data.as_str().map(inflate) {
+                Some(Ok(decoded)) => (name.to_string(), decoded),
+                _ => {
+                    warn!(topic, "failed to decode compressed topic");
+                    (topic, data)
+                }
+            }
+        })
+        .collect();
+
+    Value::Object(decoded)
+}
+
+/// Like [`listen`], but compressed topics are inflated into JSON under
+/// their un-suffixed name, `CarData.z` becomes `CarData`.
+/// Updates that fail to decode are passed through undecoded.
+pub fn listen_decoded(client: SignalrClient) -> impl Stream<Item = Vec<UpdateArgs>> {
+    listen(client).map(decode_updates)
+}
+
+pub(crate) fn decode_updates(mut updates: Vec<UpdateArgs>) -> Vec<UpdateArgs> {
+    for update in &mut updates {
+        let Some(topic) = update.topic.strip_suffix(COMPRESSED_SUFFIX) else {
+            continue;
+        };
+
+        match update.data.as_str().map(inflate) {
+            Some(Ok(data)) => {
+                update.topic = topic.to_string();
+                update.data = data;
+            }
+            _ => warn!(topic = update.topic, "failed to decode compressed update"),
+        }
+    }
+
+    updates
+}
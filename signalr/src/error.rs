@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use thiserror::Error;
+use tokio_tungstenite::tungstenite;
+
+#[derive(Debug, Error)]
+pub enum SignalrError {
+    #[error("invalid url: {0}")]
+    InvalidUrl(String),
+
+    #[error("invalid header {0}")]
+    InvalidHeader(String),
+
+    #[error("proxy error: {0}")]
+    Proxy(String),
+
+    #[error("timed out {0}")]
+    Timeout(&'static str),
+
+    #[error("negotiation request failed: {0}")]
+    Negotiation(#[from] reqwest::Error),
+
+    #[error("negotiation response has no usable set-cookie header")]
+    MissingCookie,
+
+    #[error("invalid negotiation token response: {0}")]
+    InvalidToken(#[source] serde_json::Error),
+
+    #[error("websocket handshake failed: {0}")]
+    Handshake(#[source] tungstenite::Error),
+
+    #[error("websocket error: {0}")]
+    WebSocket(#[from] tungstenite::Error),
+
+    #[error("hub invocation failed: {0}")]
+    Invocation(String),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to decode compressed payload: {0}")]
+    Decode(String),
+
+    #[error("no data received for {0:?}")]
+    Idle(Duration),
+
+    #[error("connection closed by server")]
+    Closed,
+
+    #[error("protocol error: {0}")]
+    Protocol(String),
+}
+
+impl SignalrError {
+    /// Whether reconnecting could fix this error, as opposed to a
+    /// misconfiguration or a server speaking a protocol we don't understand.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SignalrError::InvalidUrl(_)
+            | SignalrError::InvalidHeader(_)
+            | SignalrError::Invocation(_)
+            | SignalrError::Io(_)
+            | SignalrError::Decode(_)
+            | SignalrError::Protocol(_) => false,
+            SignalrError::Negotiation(err) => !err.is_builder(),
+            SignalrError::Proxy(_)
+            | SignalrError::Timeout(_)
+            | SignalrError::Idle(_)
+            | SignalrError::MissingCookie
+            | SignalrError::InvalidToken(_)
+            | SignalrError::Handshake(_)
+            | SignalrError::WebSocket(_)
+            | SignalrError::Closed => true,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SignalrError {
+    fn from(err: serde_json::Error) -> Self {
+        SignalrError::Protocol(err.to_string())
+    }
+}
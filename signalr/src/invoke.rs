@@ -0,0 +1,268 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{Instant, sleep_until},
+};
+use tracing::{debug, error, trace, warn};
+use uuid::Uuid;
+
+use crate::{SignalrError, resume::CursorTracker, transport::Transport};
+
+/// Frames waiting to be listened to. Once that many are queued the connection stops
+/// reading until the listener catches up, instead of buffering without a limit.
+const FRAMES_CAPACITY: usize = 1024;
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Invoke<'a> {
+    h: &'a str,
+    m: &'a str,
+    a: &'a [Value],
+    i: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Response {
+    i: String,
+    r: Option<Value>,
+    e: Option<String>,
+}
+
+struct Request {
+    id: String,
+    payload: String,
+    /// Also pass the response frame on to the listen streams.
+    forward: bool,
+    reply: oneshot::Sender<Result<Response, SignalrError>>,
+}
+
+struct PendingRequest {
+    id: String,
+    forward: bool,
+    reply: oneshot::Sender<Result<Response, SignalrError>>,
+}
+
+/// Cloneable handle to call hub methods on a connection while its
+/// messages keep flowing to [`crate::listen`] or [`crate::listen_raw`].
+#[derive(Clone)]
+pub struct Invoker {
+    hub: String,
+    requests: mpsc::UnboundedSender<Request>,
+}
+
+impl Invoker {
+    /// Invoke `method` on the hub, resolves once the response with the matching `I` id arrives.
+    /// Hub methods without a return value resolve to `Value::Null`.
+    pub async fn invoke(&self, method: &str, args: Vec<Value>) -> Result<Value, SignalrError> {
+        self.send(method, args, false).await
+    }
+
+    /// Like [`Invoker::invoke`], but the response frame is also passed on to
+    /// [`crate::listen_raw`] in its place among the other frames. A recording then
+    /// shows exactly which messages the result already includes, like the state
+    /// returned by `Subscribe`.
+    pub async fn invoke_forwarded(
+        &self,
+        method: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, SignalrError> {
+        self.send(method, args, true).await
+    }
+
+    async fn send(
+        &self,
+        method: &str,
+        args: Vec<Value>,
+        forward: bool,
+    ) -> Result<Value, SignalrError> {
+        let id = Uuid::new_v4().to_string();
+
+        let payload = serde_json::to_string(&Invoke {
+            h: &self.hub,
+            m: method,
+            a: &args,
+            i: &id,
+        })?;
+
+        let (reply, response) = oneshot::channel();
+
+        self.requests
+            .send(Request {
+                id: id.clone(),
+                payload,
+                forward,
+                reply,
+            })
+            .map_err(|_| SignalrError::Closed)?;
+
+        let response = response.await.map_err(|_| SignalrError::Closed)??;
+
+        if let Some(err) = response.e {
+            return Err(SignalrError::Invocation(err));
+        }
+
+        Ok(response.r.unwrap_or(Value::Null))
+    }
+}
+
+/// Why a connection ended, set once its task exits.
+#[derive(Clone, Default)]
+pub struct CloseReason(Arc<Mutex<Option<SignalrError>>>);
+
+impl CloseReason {
+    /// The error that ended the connection, `None` while it's still open
+    /// or when it was closed by us.
+    pub fn take(&self) -> Option<SignalrError> {
+        self.0.lock().unwrap().take()
+    }
+
+    fn set(&self, reason: SignalrError) {
+        *self.0.lock().unwrap() = Some(reason);
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Options {
+    /// Only resolve requests by the id of their response. Off for direct endpoints,
+    /// which don't echo ids, their responses resolve the oldest request instead.
+    pub(crate) check_response_ids: bool,
+    pub(crate) idle_timeout: Option<Duration>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            check_response_ids: true,
+            idle_timeout: None,
+        }
+    }
+}
+
+/// Move the transport into a task that sends invocations, routes responses back to
+/// their callers and forwards every other text frame to the returned receiver.
+/// The task ends when the socket closes, when no data arrived within the idle timeout,
+/// or once all invokers and the receiver are dropped.
+pub(crate) fn spawn<T: Transport>(
+    hub: &str,
+    mut transport: T,
+    cursor: CursorTracker,
+    options: Options,
+) -> (Invoker, mpsc::Receiver<String>, CloseReason) {
+    let (requests_tx, mut requests) = mpsc::unbounded_channel::<Request>();
+    let (frames_tx, frames) = mpsc::channel::<String>(FRAMES_CAPACITY);
+    let close_reason = CloseReason::default();
+
+    let reason = close_reason.clone();
+
+    tokio::spawn(async move {
+        let mut pending: Vec<PendingRequest> = Vec::new();
+        let mut invokers_dropped = false;
+        let mut last_data = Instant::now();
+
+        let err = loop {
+            let idle = async {
+                match options.idle_timeout {
+                    Some(timeout) => sleep_until(last_data + timeout).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                _ = frames_tx.closed(), if invokers_dropped => {
+                    debug!("client dropped, closing connection");
+                    transport.close().await;
+                    break None;
+                }
+                _ = idle => {
+                    let timeout = options.idle_timeout.unwrap_or_default();
+                    warn!(?timeout, "no data received, closing connection");
+                    transport.close().await;
+                    break Some(SignalrError::Idle(timeout));
+                }
+                request = requests.recv(), if !invokers_dropped => {
+                    let Some(request) = request else {
+                        invokers_dropped = true;
+                        continue;
+                    };
+
+                    trace!(id = request.id, "sending invocation");
+
+                    match transport.send(request.payload).await {
+                        Ok(_) => pending.push(PendingRequest {
+                            id: request.id,
+                            forward: request.forward,
+                            reply: request.reply,
+                        }),
+                        Err(err) => {
+                            let _ = request.reply.send(Err(err));
+                        }
+                    }
+                }
+                message = transport.recv() => {
+                    let txt = match message {
+                        Some(Ok(txt)) => txt,
+                        Some(Err(err)) => {
+                            error!(?err, "transport error");
+                            break Some(err);
+                        }
+                        None => break Some(SignalrError::Closed),
+                    };
+
+                    // keep-alives only show the socket is open, not that data is flowing
+                    if txt.trim() == "{}" {
+                        trace!("keep-alive received");
+                        continue;
+                    }
+
+                    cursor.track(&txt);
+
+                    match serde_json::from_str::<Response>(&txt) {
+                        Ok(response) => {
+                            // direct endpoints don't echo our ids, their responses resolve
+                            // the oldest request, anywhere else they're unexpected
+                            let index = pending
+                                .iter()
+                                .position(|request| request.id == response.i)
+                                .or((!options.check_response_ids && !pending.is_empty())
+                                    .then_some(0));
+
+                            match index {
+                                Some(index) => {
+                                    let request = pending.remove(index);
+                                    let _ = request.reply.send(Ok(response));
+
+                                    if request.forward {
+                                        let _ = frames_tx.send(txt).await;
+                                    }
+                                }
+                                None => warn!(id = response.i, "unexpected response"),
+                            }
+                        }
+                        Err(_) => {
+                            last_data = Instant::now();
+                            let _ = frames_tx.send(txt).await;
+                        }
+                    }
+                }
+            }
+        };
+
+        if let Some(err) = err {
+            reason.set(err);
+        }
+    });
+
+    let invoker = Invoker {
+        hub: hub.to_string(),
+        requests: requests_tx,
+    };
+
+    (invoker, frames, close_reason)
+}
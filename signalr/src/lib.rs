@@ -1,124 +1,121 @@
-use std::{env, str::FromStr};
-
-use futures::{SinkExt, Stream};
-use reqwest::{
-    Url,
-    header::{self, HeaderValue},
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+use tokio_stream::{StreamExt, wrappers::ReceiverStream};
+use tracing::{trace, warn};
+
+use crate::{invoke::Options, resume::CursorTracker};
+
+mod builder;
+mod decode;
+mod error;
+mod invoke;
+mod proxy;
+mod reconnect;
+mod resume;
+mod timestamp;
+mod transport;
+
+pub use builder::{ClientBuilder, NegotiationResponse, Protocol};
+pub use decode::{COMPRESSED_SUFFIX, decode_state, inflate, listen_decoded};
+pub use error::SignalrError;
+pub use invoke::{CloseReason, Invoker};
+pub use reconnect::{Event, ReconnectPolicy, ReconnectingClient, Resubscriber, reconnecting};
+pub use resume::{Cursor, ResumeHandle, reconnect};
+pub use timestamp::parse_timestamp;
+pub use transport::{
+    ChannelTransport, CoreTransport, FileTransport, Transport, WebSocketTransport,
 };
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use tokio_stream::StreamExt;
-use tokio_tungstenite::tungstenite::{Message, client::IntoClientRequest, http::Request};
-use tracing::{debug, error, info, trace};
-use uuid::Uuid;
-
-#[derive(Serialize)]
-struct Connection {
-    name: String,
-}
-
-#[derive(Serialize)]
-struct ConnectionData([Connection; 1]);
-
-#[derive(Deserialize, Debug)]
-#[serde(rename_all = "PascalCase")]
-pub struct NegotiationResponse {
-    pub connection_token: String,
-}
-
-struct Negotiation {
-    token: String,
-    cookie: String,
-}
-
-async fn negotiate(url: &str, hub: &str) -> Result<Negotiation, anyhow::Error> {
-    let hub = ConnectionData([Connection {
-        name: hub.to_string(),
-    }]);
-
-    let hub_param = serde_json::to_string(&hub)?;
-
-    let url = Url::parse_with_params(
-        &format!("https://{}/negotiate", url),
-        &[("clientProtocol", "1.5"), ("connectionData", &hub_param)],
-    )?;
-
-    let req = reqwest::get(url).await?;
-
-    let headers = req.headers().clone();
-    let res: NegotiationResponse = serde_json::from_str(&req.text().await?)?;
 
-    let cookie = headers[header::SET_COOKIE].to_str()?.to_string();
-
-    Ok(Negotiation {
-        token: res.connection_token,
-        cookie,
-    })
-}
-
-type WsStream =
+pub type WsStream =
     tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
 
 pub struct SignalrClient {
     pub hub: String,
-    pub stream: WsStream,
+    invoker: Invoker,
+    frames: mpsc::Receiver<String>,
+    cursor: CursorTracker,
+    resume: Option<ResumeHandle>,
+    close_reason: CloseReason,
 }
 
-pub async fn create_client(url: &str, hub: &str) -> Result<SignalrClient, anyhow::Error> {
-    let negotiation = negotiate(url, hub).await?;
-
-    let url = Url::parse_with_params(
-        &format!("wss://{}/connect", url),
-        &[
-            ("clientProtocol", "1.5"),
-            ("transport", "webSockets"),
-            ("connectionToken", &negotiation.token),
-        ],
-    )?;
-
-    let url = match env::var_os("F1_DEV_URL") {
-        Some(env_url) => Url::from_str(&env_url.into_string().unwrap())?,
-        None => url,
-    };
-
-    info!("connecting to {url}");
+impl SignalrClient {
+    /// Client for an already established connection, like an in-memory
+    /// [`ChannelTransport`] or a recording read by [`FileTransport`].
+    /// It can't be resumed with [`reconnect`].
+    pub fn from_transport(hub: &str, transport: impl Transport) -> Self {
+        Self::spawn(
+            hub,
+            transport,
+            CursorTracker::default(),
+            None,
+            Options::default(),
+        )
+    }
 
-    let mut req: Request<()> = url.into_client_request()?;
+    pub(crate) fn resumable(transport: impl Transport, resume: ResumeHandle) -> Self {
+        let hub = resume.builder.hub().to_string();
+        let options = resume.builder.options();
+        Self::spawn(
+            &hub,
+            transport,
+            resume.cursor.clone(),
+            Some(resume),
+            options,
+        )
+    }
 
-    let headers = req.headers_mut();
-    headers.insert(header::USER_AGENT, HeaderValue::from_static("BestHTTP"));
-    headers.insert(
-        header::ACCEPT_ENCODING,
-        HeaderValue::from_static("gzip,identity"),
-    );
-    headers.insert(header::COOKIE, negotiation.cookie.parse()?);
+    pub(crate) fn spawn(
+        hub: &str,
+        transport: impl Transport,
+        cursor: CursorTracker,
+        resume: Option<ResumeHandle>,
+        options: Options,
+    ) -> Self {
+        let (invoker, frames, close_reason) =
+            invoke::spawn(hub, transport, cursor.clone(), options);
+
+        Self {
+            hub: hub.to_string(),
+            invoker,
+            frames,
+            cursor,
+            resume,
+            close_reason,
+        }
+    }
 
-    let (stream, res) = tokio_tungstenite::connect_async(req).await?;
+    /// Invoke a hub method, see [`Invoker::invoke`].
+    pub async fn invoke(&self, method: &str, args: Vec<Value>) -> Result<Value, SignalrError> {
+        self.invoker.invoke(method, args).await
+    }
 
-    debug!(?res, "ws connected");
+    /// Handle to invoke hub methods after the client was consumed by [`listen`] or [`listen_raw`].
+    pub fn invoker(&self) -> Invoker {
+        self.invoker.clone()
+    }
 
-    let client = SignalrClient {
-        hub: hub.to_string(),
-        stream,
-    };
+    /// Message id and groups token received so far.
+    pub fn cursor(&self) -> Cursor {
+        self.cursor.get()
+    }
 
-    Ok(client)
-}
+    /// Why the connection ended, take it before listening and check it once the stream ends.
+    pub fn close_reason(&self) -> CloseReason {
+        self.close_reason.clone()
+    }
 
-#[derive(Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct Invoke {
-    h: String,
-    m: String,
-    a: Vec<Vec<String>>,
-    i: String,
+    /// Handle to [`reconnect`] this connection later, take it before listening.
+    /// `None` for clients created with [`SignalrClient::from_transport`].
+    pub fn resume_handle(&self) -> Option<ResumeHandle> {
+        self.resume.clone()
+    }
 }
 
-#[derive(Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct Response {
-    i: String,
-    r: Option<serde_json::Value>,
+pub async fn create_client(url: &str, hub: &str) -> Result<SignalrClient, SignalrError> {
+    ClientBuilder::new(url, hub).connect().await
 }
 
 #[derive(Deserialize)]
@@ -133,107 +130,67 @@ struct Args {
     a: (String, serde_json::Value, String),
 }
 
+#[derive(Debug)]
 pub struct UpdateArgs {
     pub topic: String,
     pub data: serde_json::Value,
-    pub timestamp: String,
+    pub timestamp: DateTime<Utc>,
 }
 
-pub async fn subscribe(
-    client: &mut SignalrClient,
-    topics: &[&str],
-) -> Result<Value, anyhow::Error> {
-    let id = Uuid::new_v4().to_string();
-
-    let invoke_message = Invoke {
-        h: client.hub.clone(),
-        m: "Subscribe".to_string(),
-        a: vec![topics.iter().map(|&s| s.to_string()).collect()],
-        i: id.clone(),
-    };
-
-    let subscribe_message = serde_json::to_string(&invoke_message)?;
-
-    client.stream.send(Message::text(subscribe_message)).await?;
-
-    let response = receive_valid_response(&mut client.stream).await?;
+/// Subscribe to `topics`, returns the initial state of all of them.
+pub async fn subscribe(client: &mut SignalrClient, topics: &[&str]) -> Result<Value, SignalrError> {
+    let result = client.invoke("Subscribe", vec![json!(topics)]).await?;
 
-    if response.i != id && env::var_os("F1_DEV_URL").is_none() {
-        return Err(anyhow::anyhow!("Response ID does not match request ID"));
-    }
-
-    if let Some(result) = response.r {
-        Ok(result)
-    } else {
-        Err(anyhow::anyhow!("No result in response"))
-    }
-}
-
-async fn receive_valid_response(stream: &mut WsStream) -> Result<Response, anyhow::Error> {
-    loop {
-        let response_message = stream
-            .next()
-            .await
-            .ok_or_else(|| anyhow::anyhow!("No response received"))??;
-
-        if let Message::Text(txt) = response_message
-            && let Ok(response) = serde_json::from_str::<Response>(&txt)
-        {
-            return Ok(response);
-        }
+    match result {
+        Value::Null => Err(SignalrError::Protocol("no result in response".to_string())),
+        result => Ok(result),
     }
 }
 
 /// Listen to WebSocket messages and parse them into structured UpdateArgs.
 /// This is useful when you need to process the data programmatically.
 pub fn listen(client: SignalrClient) -> impl Stream<Item = Vec<UpdateArgs>> {
-    client
-        .stream
-        .filter_map(|message| {
-            trace!("message received");
-
-            match message {
-                Ok(message) => match message {
-                    Message::Text(txt) => serde_json::from_str::<Update>(txt.as_str()).ok(),
-                    _ => None,
-                },
-                Err(err) => {
-                    error!(?err, "ws error");
-                    None
-                }
-            }
-        })
-        .filter_map(|update| {
-            let mut updates = Vec::new();
-
-            for args in update.m {
-                let (topic, data, timestamp) = args.a;
-                updates.push(UpdateArgs {
-                    topic,
-                    data,
-                    timestamp,
-                });
-            }
-
-            Some(updates)
-        })
+    listen_raw(client).filter_map(|txt| parse_updates(&txt))
+}
+
+/// The updates of a `{"M":[...]}` frame, as received by [`listen_raw`] or saved in a
+/// recording. `None` for any other frame.
+pub fn parse_updates(txt: &str) -> Option<Vec<UpdateArgs>> {
+    let update = serde_json::from_str::<Update>(txt).ok()?;
+
+    let mut updates = Vec::new();
+
+    for args in update.m {
+        let (topic, data, timestamp) = args.a;
+
+        let timestamp = parse_timestamp(&timestamp).unwrap_or_else(|| {
+            warn!(
+                timestamp,
+                "failed to parse update timestamp, using receive time"
+            );
+            Utc::now()
+        });
+
+        updates.push(UpdateArgs {
+            topic,
+            data,
+            timestamp,
+        });
+    }
+
+    Some(updates)
 }
 
 /// Listen to raw WebSocket messages without parsing them.
 /// Returns the raw text messages as-is, useful for saving to a file for replay.
+/// Responses to invocations are not included.
 pub fn listen_raw(client: SignalrClient) -> impl Stream<Item = String> {
-    client.stream.filter_map(|message| {
-        trace!("raw message received");
-
-        match message {
-            Ok(message) => match message {
-                Message::Text(txt) => Some(txt.to_string()),
-                _ => None,
-            },
-            Err(err) => {
-                error!(?err, "ws error");
-                None
-            }
-        }
+    // keep the invoker alive for as long as the stream, it owns the connection
+    let invoker = client.invoker;
+
+    ReceiverStream::new(client.frames).map(move |txt| {
+        let _ = &invoker;
+        trace!("message received");
+        txt
     })
 }
@@ -0,0 +1,110 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use reqwest::Url;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_socks::tcp::Socks5Stream;
+use tracing::debug;
+
+use crate::SignalrError;
+
+/// Open a TCP connection to the host of `target` tunneled through `proxy`.
+pub(crate) async fn connect(proxy: &str, target: &Url) -> Result<TcpStream, SignalrError> {
+    let proxy = Url::parse(proxy).map_err(|err| SignalrError::InvalidUrl(err.to_string()))?;
+
+    let proxy_addr = (
+        proxy
+            .host_str()
+            .ok_or_else(|| SignalrError::InvalidUrl(format!("proxy {proxy} has no host")))?,
+        proxy
+            .port_or_known_default()
+            .ok_or_else(|| SignalrError::InvalidUrl(format!("proxy {proxy} has no port")))?,
+    );
+
+    let target_host = target
+        .host_str()
+        .ok_or_else(|| SignalrError::InvalidUrl(format!("{target} has no host")))?;
+    let target_port = target
+        .port_or_known_default()
+        .ok_or_else(|| SignalrError::InvalidUrl(format!("{target} has no port")))?;
+
+    debug!(proxy = %proxy, "connecting through proxy");
+
+    let credentials = match proxy.username() {
+        "" => None,
+        username => Some((username, proxy.password().unwrap_or_default())),
+    };
+
+    match proxy.scheme() {
+        "http" => {
+            // io errors of the proxy are as retryable as the proxy refusing the tunnel
+            let mut stream = TcpStream::connect(proxy_addr)
+                .await
+                .map_err(|err| SignalrError::Proxy(format!("failed to connect: {err}")))?;
+            http_connect(&mut stream, target_host, target_port, credentials).await?;
+            Ok(stream)
+        }
+        "socks5" | "socks5h" => {
+            let target = (target_host, target_port);
+
+            let stream = match credentials {
+                Some((username, password)) => {
+                    Socks5Stream::connect_with_password(proxy_addr, target, username, password)
+                        .await
+                }
+                None => Socks5Stream::connect(proxy_addr, target).await,
+            }
+            .map_err(|err| SignalrError::Proxy(err.to_string()))?;
+
+            Ok(stream.into_inner())
+        }
+        scheme => Err(SignalrError::InvalidUrl(format!(
+            "unsupported proxy scheme {scheme}"
+        ))),
+    }
+}
+
+async fn http_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    credentials: Option<(&str, &str)>,
+) -> Result<(), SignalrError> {
+    let mut request = format!("CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n");
+
+    if let Some((username, password)) = credentials {
+        let auth = BASE64_STANDARD.encode(format!("{username}:{password}"));
+        request.push_str(&format!("Proxy-Authorization: Basic {auth}\r\n"));
+    }
+
+    request.push_str("\r\n");
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .map_err(|err| SignalrError::Proxy(format!("failed to send CONNECT: {err}")))?;
+
+    // read byte by byte, anything after the headers already belongs to the tunnel
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() > 8 * 1024 {
+            return Err(SignalrError::Proxy(
+                "response headers too large".to_string(),
+            ));
+        }
+
+        let byte = stream.read_u8().await.map_err(|err| {
+            SignalrError::Proxy(format!("failed to read CONNECT response: {err}"))
+        })?;
+
+        response.push(byte);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status = response.lines().next().unwrap_or_default();
+
+    match status.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        _ => Err(SignalrError::Proxy(format!("CONNECT failed: {status}"))),
+    }
+}
@@ -0,0 +1,314 @@
+use std::{sync::Arc, time::Duration};
+
+use async_stream::stream;
+use futures::{Stream, future::BoxFuture};
+use serde_json::{Value, json};
+use tokio::{sync::Notify, time::Instant};
+use tokio_stream::StreamExt;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    ClientBuilder, Invoker, ResumeHandle, SignalrClient, SignalrError, UpdateArgs,
+    decode::{decode_state, decode_updates},
+    listen, listen_raw, reconnect, subscribe,
+};
+
+/// Backoff settings used by [`ReconnectingClient`] between connection attempts.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    /// Consecutive failed attempts after which the stream ends, `None` retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Exponential delay for the given attempt (starting at 1), with equal jitter
+    /// so replicas that dropped at the same time don't reconnect in lockstep.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = self
+            .multiplier
+            .powi(attempt.saturating_sub(1).min(i32::MAX as u32) as i32);
+
+        // `mul_f64` panics on overflow, a long outage grows the delay past any `Duration`
+        let base = Duration::try_from_secs_f64(self.initial_delay.as_secs_f64() * exp)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+            .as_millis() as u64;
+
+        let half = base / 2;
+        Duration::from_millis(half + fastrand::u64(0..=half))
+    }
+}
+
+/// Events emitted by a [`ReconnectingClient`] stream.
+#[derive(Debug)]
+pub enum Event<T> {
+    /// Subscribed (or re-subscribed after a reconnect), carries the initial state.
+    Subscribed(Value),
+    /// Resumed the previous connection after a short disconnect, missed
+    /// messages are replayed by the server so the current state stays valid.
+    Resumed,
+    /// A message received on the current connection.
+    Message(T),
+    /// The connection was lost or could not be established, retrying after `delay`.
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+        reason: SignalrError,
+    },
+    /// The error is not retryable or the policy ran out of attempts, the stream ends.
+    Failed(SignalrError),
+}
+
+/// A client that resumes the previous connection when the underlying connection
+/// drops, and falls back to re-negotiating and re-subscribing to the same topics.
+pub struct ReconnectingClient {
+    builder: ClientBuilder,
+    topics: Vec<String>,
+    policy: ReconnectPolicy,
+    snapshot_interval: Option<Duration>,
+    resubscribe: Arc<Notify>,
+}
+
+/// Asks a [`ReconnectingClient`] to call `Subscribe` again on its running connection,
+/// the full state it returns arrives as [`Event::Subscribed`] among the messages.
+#[derive(Clone)]
+pub struct Resubscriber(Arc<Notify>);
+
+impl Resubscriber {
+    /// Requests made while one is still in flight are answered by that one.
+    pub fn resubscribe(&self) {
+        self.0.notify_one();
+    }
+}
+
+/// What woke up the loop of a running connection.
+enum Step<T> {
+    Message(Option<T>),
+    Requested,
+    Resubscribed(Result<Value, SignalrError>),
+}
+
+/// Reconnecting client with the default connection settings,
+/// use [`ClientBuilder::reconnecting`] to configure them.
+pub fn reconnecting(
+    url: &str,
+    hub: &str,
+    topics: &[&str],
+    policy: ReconnectPolicy,
+) -> ReconnectingClient {
+    ClientBuilder::new(url, hub).reconnecting(topics, policy)
+}
+
+impl ReconnectingClient {
+    pub(crate) fn new(builder: ClientBuilder, topics: &[&str], policy: ReconnectPolicy) -> Self {
+        Self {
+            builder,
+            topics: topics.iter().map(|&s| s.to_string()).collect(),
+            policy,
+            snapshot_interval: None,
+            resubscribe: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Take before the client is consumed by one of the listen functions.
+    pub fn resubscriber(&self) -> Resubscriber {
+        Resubscriber(self.resubscribe.clone())
+    }
+
+    /// Subscribe again every `interval` on the running connection, so the server sends
+    /// its full state. The response frames show up in [`ReconnectingClient::listen_raw`]
+    /// in order, making recordings checkable against the upstream state.
+    pub fn snapshot_interval(mut self, interval: Duration) -> Self {
+        self.snapshot_interval = Some(interval);
+        self
+    }
+
+    /// Parsed updates, see [`listen`].
+    pub fn listen(self) -> impl Stream<Item = Event<Vec<UpdateArgs>>> {
+        self.run(listen)
+    }
+
+    /// Updates with compressed topics inflated, see [`crate::listen_decoded`].
+    /// The initial state of `Subscribed` events is decoded as well.
+    pub fn listen_decoded(self) -> impl Stream<Item = Event<Vec<UpdateArgs>>> {
+        self.listen().map(|event| match event {
+            Event::Subscribed(initial) => Event::Subscribed(decode_state(initial)),
+            Event::Message(updates) => Event::Message(decode_updates(updates)),
+            event => event,
+        })
+    }
+
+    /// Raw text messages, see [`listen_raw`].
+    pub fn listen_raw(self) -> impl Stream<Item = Event<String>> {
+        self.run(listen_raw)
+    }
+
+    fn run<T, S, F>(self, listen: F) -> impl Stream<Item = Event<T>>
+    where
+        F: Fn(SignalrClient) -> S,
+        S: Stream<Item = T>,
+    {
+        stream! {
+            let topics = self.topics.iter().map(String::as_str).collect::<Vec<&str>>();
+            let mut attempt: u32 = 0;
+            let mut resume: Option<ResumeHandle> = None;
+
+            loop {
+                let reason = match self.connect(resume.take(), &topics).await {
+                    Ok((client, initial)) => {
+                        match initial {
+                            Some(initial) => yield Event::Subscribed(initial),
+                            None => yield Event::Resumed,
+                        }
+
+                        resume = client.resume_handle();
+
+                        let close_reason = client.close_reason();
+                        let invoker = client.invoker();
+                        let messages = listen(client);
+                        tokio::pin!(messages);
+
+                        let snapshots = self.snapshots(invoker.clone(), &topics);
+                        tokio::pin!(snapshots);
+
+                        let mut resubscribing: Option<BoxFuture<'static, Result<Value, SignalrError>>> = None;
+                        let mut failed: Option<SignalrError> = None;
+
+                        loop {
+                            let step = tokio::select! {
+                                message = messages.next() => Step::Message(message),
+                                _ = self.resubscribe.notified(), if resubscribing.is_none() => Step::Requested,
+                                result = async { resubscribing.as_mut().expect("checked by the condition").await },
+                                    if resubscribing.is_some() => Step::Resubscribed(result),
+                                _ = &mut snapshots => continue,
+                            };
+
+                            match step {
+                                Step::Message(Some(message)) => {
+                                    // only reset the backoff once the connection proved to be working
+                                    attempt = 0;
+                                    yield Event::Message(message);
+                                }
+                                Step::Message(None) => break,
+                                Step::Requested => {
+                                    debug!("subscribing again on the running connection");
+
+                                    let invoker = invoker.clone();
+                                    let args = vec![json!(topics)];
+
+                                    resubscribing = Some(Box::pin(async move {
+                                        invoker.invoke("Subscribe", args).await
+                                    }));
+                                }
+                                Step::Resubscribed(Ok(initial)) => {
+                                    resubscribing = None;
+                                    yield Event::Subscribed(initial);
+                                }
+                                Step::Resubscribed(Err(err)) => {
+                                    warn!(?err, "failed to subscribe again, reconnecting");
+                                    failed = Some(err);
+                                    break;
+                                }
+                            }
+                        }
+
+                        let reason = failed
+                            .take()
+                            .or_else(|| close_reason.take())
+                            .unwrap_or(SignalrError::Closed);
+                        warn!(%reason, "signalr connection closed");
+
+                        // a stalled feed wouldn't recover by resuming it, and a requested
+                        // state only comes with subscribing again
+                        if matches!(reason, SignalrError::Idle(_)) || resubscribing.is_some() {
+                            resume = None;
+                        }
+
+                        reason
+                    }
+                    Err(err) => {
+                        error!(?err, "failed to connect to signalr");
+                        err
+                    }
+                };
+
+                attempt += 1;
+
+                if !reason.is_retryable() {
+                    error!(?reason, "not retrying signalr connection");
+                    yield Event::Failed(reason);
+                    break;
+                }
+
+                if self.policy.max_attempts.is_some_and(|max| attempt > max) {
+                    error!(attempt, "giving up reconnecting to signalr");
+                    yield Event::Failed(reason);
+                    break;
+                }
+
+                let delay = self.policy.delay(attempt);
+                info!(attempt, ?delay, "reconnecting to signalr");
+                yield Event::Reconnecting { attempt, delay, reason };
+
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    /// Invokes `Subscribe` every snapshot interval, never completes.
+    async fn snapshots(&self, invoker: Invoker, topics: &[&str]) {
+        let Some(interval) = self.snapshot_interval else {
+            return std::future::pending().await;
+        };
+
+        let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+
+        loop {
+            ticker.tick().await;
+
+            // don't hold up the messages while waiting for the response
+            let invoker = invoker.clone();
+            let args = vec![json!(topics)];
+
+            tokio::spawn(async move {
+                match invoker.invoke_forwarded("Subscribe", args).await {
+                    Ok(_) => debug!("received state snapshot"),
+                    Err(err) => warn!(?err, "failed to request state snapshot"),
+                }
+            });
+        }
+    }
+
+    /// Resume when possible, otherwise connect and subscribe from scratch,
+    /// in which case the initial state is returned as well.
+    async fn connect(
+        &self,
+        resume: Option<ResumeHandle>,
+        topics: &[&str],
+    ) -> Result<(SignalrClient, Option<Value>), SignalrError> {
+        if let Some(handle) = resume {
+            match reconnect(&handle).await {
+                Ok(client) => return Ok((client, None)),
+                Err(err) => warn!(?err, "failed to resume connection, subscribing again"),
+            }
+        }
+
+        let mut client = self.builder.connect().await?;
+        let initial = subscribe(&mut client, topics).await?;
+        Ok((client, Some(initial)))
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::{Arc, Mutex};
+
+use serde::Deserialize;
+use tracing::{debug, trace};
+
+use crate::{ClientBuilder, SignalrClient, SignalrError, builder::Negotiation};
+
+/// Position in the message stream, taken from the `C` (message id)
+/// and `G` (groups token) fields of the frames received so far.
+#[derive(Debug, Clone, Default)]
+pub struct Cursor {
+    pub message_id: Option<String>,
+    pub groups_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FramePosition {
+    c: Option<String>,
+    g: Option<String>,
+}
+
+/// Cursor shared between a client's connection task and its [`ResumeHandle`].
+#[derive(Clone, Default)]
+pub(crate) struct CursorTracker(Arc<Mutex<Cursor>>);
+
+impl CursorTracker {
+    pub(crate) fn get(&self) -> Cursor {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub(crate) fn track(&self, txt: &str) {
+        let Ok(position) = serde_json::from_str::<FramePosition>(txt) else {
+            return;
+        };
+
+        if position.c.is_none() && position.g.is_none() {
+            return;
+        }
+
+        let mut cursor = self.0.lock().unwrap();
+
+        if let Some(message_id) = position.c {
+            trace!(message_id, "tracked message id");
+            cursor.message_id = Some(message_id);
+        }
+
+        if let Some(groups_token) = position.g {
+            cursor.groups_token = Some(groups_token);
+        }
+    }
+}
+
+/// Everything needed to resume a dropped connection with [`reconnect`].
+/// The cursor is shared with the client, so a handle taken before
+/// listening keeps tracking the stream.
+#[derive(Clone)]
+pub struct ResumeHandle {
+    pub(crate) builder: ClientBuilder,
+    pub(crate) negotiation: Negotiation,
+    pub(crate) cursor: CursorTracker,
+}
+
+impl ResumeHandle {
+    pub fn cursor(&self) -> Cursor {
+        self.cursor.get()
+    }
+}
+
+/// Resume a connection through the `/reconnect` endpoint, the server replays
+/// everything after the tracked message id instead of us subscribing again.
+/// Fails if nothing was received yet or the server already dropped the connection.
+pub async fn reconnect(handle: &ResumeHandle) -> Result<SignalrClient, SignalrError> {
+    let cursor = handle.cursor();
+
+    let message_id = cursor
+        .message_id
+        .ok_or_else(|| SignalrError::Protocol("no message id to resume from".to_string()))?;
+
+    let builder = &handle.builder;
+
+    let mut params = vec![
+        ("clientProtocol", builder.protocol_version()),
+        ("transport", "webSockets"),
+        ("connectionToken", handle.negotiation.token.as_str()),
+        (
+            "connectionData",
+            handle.negotiation.connection_data.as_str(),
+        ),
+        ("messageId", message_id.as_str()),
+    ];
+
+    if let Some(groups_token) = cursor.groups_token.as_deref() {
+        params.push(("groupsToken", groups_token));
+    }
+
+    let url = builder.url("reconnect", &params)?;
+
+    debug!(message_id, "resuming connection");
+
+    let stream = builder.open(url, Some(&handle.negotiation.cookie)).await?;
+
+    Ok(SignalrClient::resumable(
+        builder.transport(stream),
+        handle.clone(),
+    ))
+}
@@ -0,0 +1,13 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+/// Parse a feed timestamp, which comes with anywhere from zero to seven
+/// fractional second digits and doesn't always carry a `Z` suffix.
+pub fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(timestamp) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+
+    NaiveDateTime::parse_from_str(timestamp.trim_end_matches('Z'), "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .map(|naive| naive.and_utc())
+}
@@ -0,0 +1,28 @@
+use std::future::Future;
+
+use crate::SignalrError;
+
+mod channel;
+mod core;
+mod file;
+mod websocket;
+
+pub use self::core::CoreTransport;
+pub use channel::ChannelTransport;
+pub use file::FileTransport;
+pub use websocket::WebSocketTransport;
+
+/// A connection that carries SignalR text frames in both directions.
+///
+/// `recv` must be cancel safe, the client drops a pending `recv` whenever
+/// it has a frame to send.
+pub trait Transport: Send + 'static {
+    /// Send a text frame.
+    fn send(&mut self, text: String) -> impl Future<Output = Result<(), SignalrError>> + Send;
+
+    /// Receive the next text frame, `None` once the connection is closed.
+    fn recv(&mut self) -> impl Future<Output = Option<Result<String, SignalrError>>> + Send;
+
+    /// Close the connection.
+    fn close(&mut self) -> impl Future<Output = ()> + Send;
+}
@@ -0,0 +1,45 @@
+use tokio::sync::mpsc;
+
+use crate::{SignalrError, transport::Transport};
+
+/// In-memory transport, frames sent on one end of a [`ChannelTransport::pair`]
+/// are received on the other. Useful to stand in for a server in tests.
+pub struct ChannelTransport {
+    tx: Option<mpsc::UnboundedSender<String>>,
+    rx: mpsc::UnboundedReceiver<String>,
+}
+
+impl ChannelTransport {
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, a_rx) = mpsc::unbounded_channel();
+        let (b_tx, b_rx) = mpsc::unbounded_channel();
+
+        let a = Self {
+            tx: Some(a_tx),
+            rx: b_rx,
+        };
+        let b = Self {
+            tx: Some(b_tx),
+            rx: a_rx,
+        };
+
+        (a, b)
+    }
+}
+
+impl Transport for ChannelTransport {
+    async fn send(&mut self, text: String) -> Result<(), SignalrError> {
+        self.tx
+            .as_ref()
+            .and_then(|tx| tx.send(text).ok())
+            .ok_or(SignalrError::Closed)
+    }
+
+    async fn recv(&mut self) -> Option<Result<String, SignalrError>> {
+        self.rx.recv().await.map(Ok)
+    }
+
+    async fn close(&mut self) {
+        self.tx = None;
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::VecDeque, time::Duration};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::time::{Instant, sleep_until};
+use tracing::{debug, trace};
+
+use crate::{SignalrError, transport::Transport};
+
+/// Separates messages of the ASP.NET Core SignalR JSON hub protocol.
+const RECORD_SEPARATOR: char = '\u{1e}';
+
+/// Core servers drop clients that stay silent for 30 seconds by default.
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ClassicInvoke {
+    m: String,
+    a: Vec<Value>,
+    i: String,
+}
+
+#[derive(Deserialize)]
+struct HandshakeResponse {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    #[serde(rename = "type")]
+    kind: u8,
+    invocation_id: Option<String>,
+    target: Option<String>,
+    #[serde(default)]
+    arguments: Vec<Value>,
+    item: Option<Value>,
+    result: Option<Value>,
+    error: Option<String>,
+    allow_reconnect: Option<bool>,
+}
+
+/// Speaks the ASP.NET Core SignalR JSON hub protocol over another transport.
+///
+/// Messages are translated from and to the classic 1.5 frame shape, invocations
+/// become `{"M":[{"H","M","A"}]}` updates, stream items `{"P":{"I","D"}}` progress
+/// frames, completions `{"I","R"}` responses and pings `{}` keep-alives, so the rest of the client (and recordings made with
+/// `listen_raw`) don't depend on the protocol.
+pub struct CoreTransport<T: Transport> {
+    inner: T,
+    hub: String,
+    queue: VecDeque<String>,
+    last_sent: Instant,
+}
+
+impl<T: Transport> CoreTransport<T> {
+    /// Perform the protocol handshake on a freshly opened connection.
+    pub async fn handshake(mut inner: T, hub: &str) -> Result<Self, SignalrError> {
+        inner
+            .send(format!(
+                r#"{{"protocol":"json","version":1}}{RECORD_SEPARATOR}"#
+            ))
+            .await?;
+
+        let txt = inner.recv().await.ok_or(SignalrError::Closed)??;
+
+        let mut records = split(&txt);
+
+        let handshake = records
+            .pop_front()
+            .ok_or_else(|| SignalrError::Protocol("empty handshake response".to_string()))?;
+
+        let handshake = serde_json::from_str::<HandshakeResponse>(&handshake)
+            .map_err(|err| SignalrError::Protocol(format!("invalid handshake response: {err}")))?;
+
+        if let Some(err) = handshake.error {
+            return Err(SignalrError::Protocol(format!("handshake rejected: {err}")));
+        }
+
+        debug!("core handshake completed");
+
+        let mut transport = Self {
+            inner,
+            hub: hub.to_string(),
+            queue: VecDeque::new(),
+            last_sent: Instant::now(),
+        };
+
+        for record in records {
+            transport.push(&record)?;
+        }
+
+        Ok(transport)
+    }
+
+    /// Translate a received record into a classic frame and queue it.
+    fn push(&mut self, record: &str) -> Result<(), SignalrError> {
+        let message = serde_json::from_str::<Message>(record)?;
+
+        let frame = match message.kind {
+            1 => json!({
+                "M": [{
+                    "H": self.hub,
+                    "M": message.target.unwrap_or_default(),
+                    "A": message.arguments,
+                }]
+            }),
+            // stream items carry no target, only the item of the invocation they belong to
+            2 => match message.invocation_id {
+                Some(id) => json!({ "P": { "I": id, "D": message.item } }),
+                None => {
+                    trace!("ignoring stream item without invocation id");
+                    return Ok(());
+                }
+            },
+            3 => match (message.invocation_id, message.error) {
+                (Some(id), Some(err)) => json!({ "I": id, "E": err }),
+                (Some(id), None) => json!({ "I": id, "R": message.result }),
+                (None, _) => return Ok(()),
+            },
+            6 => json!({}),
+            7 => {
+                return match message.error {
+                    Some(err) if message.allow_reconnect != Some(true) => {
+                        Err(SignalrError::Protocol(format!("server closed: {err}")))
+                    }
+                    _ => Err(SignalrError::Closed),
+                };
+            }
+            kind => {
+                trace!(kind, "ignoring core message");
+                return Ok(());
+            }
+        };
+
+        self.queue.push_back(frame.to_string());
+
+        Ok(())
+    }
+}
+
+impl<T: Transport> Transport for CoreTransport<T> {
+    async fn send(&mut self, text: String) -> Result<(), SignalrError> {
+        let invoke = serde_json::from_str::<ClassicInvoke>(&text)?;
+
+        let message = json!({
+            "type": 1,
+            "invocationId": invoke.i,
+            "target": invoke.m,
+            "arguments": invoke.a,
+        });
+
+        self.last_sent = Instant::now();
+        self.inner
+            .send(format!("{message}{RECORD_SEPARATOR}"))
+            .await
+    }
+
+    async fn recv(&mut self) -> Option<Result<String, SignalrError>> {
+        loop {
+            if let Some(frame) = self.queue.pop_front() {
+                return Some(Ok(frame));
+            }
+
+            tokio::select! {
+                txt = self.inner.recv() => {
+                    let txt = match txt? {
+                        Ok(txt) => txt,
+                        Err(err) => return Some(Err(err)),
+                    };
+
+                    for record in split(&txt) {
+                        if let Err(err) = self.push(&record) {
+                            return match err {
+                                SignalrError::Closed => None,
+                                err => Some(Err(err)),
+                            };
+                        }
+                    }
+                }
+                _ = sleep_until(self.last_sent + PING_INTERVAL) => {
+                    trace!("sending core ping");
+                    self.last_sent = Instant::now();
+
+                    let ping = format!(r#"{{"type":6}}{RECORD_SEPARATOR}"#);
+                    if let Err(err) = self.inner.send(ping).await {
+                        return Some(Err(err));
+                    }
+                }
+            }
+        }
+    }
+
+    async fn close(&mut self) {
+        let _ = self
+            .inner
+            .send(format!(r#"{{"type":7}}{RECORD_SEPARATOR}"#))
+            .await;
+        self.inner.close().await;
+    }
+}
+
+fn split(txt: &str) -> VecDeque<String> {
+    txt.split(RECORD_SEPARATOR)
+        .filter(|record| !record.is_empty())
+        .map(str::to_string)
+        .collect()
+}
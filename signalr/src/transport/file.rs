@@ -0,0 +1,168 @@
+use std::{collections::VecDeque, path::Path, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, de::IgnoredAny};
+use serde_json::json;
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, BufReader, Lines},
+    time::{Instant, sleep_until},
+};
+use tracing::debug;
+
+use crate::{SignalrError, parse_timestamp, transport::Transport};
+
+/// Longer pauses in a paced recording are skipped, like the gap of a reconnect.
+const MAX_GAP: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Invocation {
+    m: String,
+    i: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Frame {
+    m: Vec<Args>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Args {
+    a: (IgnoredAny, IgnoredAny, String),
+}
+
+/// Transport reading a line-delimited recording, as written by `simulator save`.
+///
+/// The first line is the initial state, it's returned as the result of the first
+/// `Subscribe` invocation. Every following line is received as a frame after that,
+/// as fast as it's read unless [`FileTransport::paced`]. Other invocations resolve
+/// without a result.
+pub struct FileTransport {
+    lines: Lines<BufReader<File>>,
+    initial: Option<String>,
+    responses: VecDeque<String>,
+    speed: Option<f64>,
+    /// The feed time the recording is at and when that was replayed.
+    clock: Option<(DateTime<Utc>, Instant)>,
+    /// Read line waiting for its time, kept here so `recv` stays cancel safe.
+    next: Option<(String, Instant)>,
+}
+
+impl FileTransport {
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, SignalrError> {
+        let file = File::open(path).await?;
+        let mut lines = BufReader::new(file).lines();
+
+        let initial = lines.next_line().await?;
+
+        Ok(Self {
+            lines,
+            initial,
+            responses: VecDeque::new(),
+            speed: None,
+            clock: None,
+            next: None,
+        })
+    }
+
+    /// Replay the frames with the time between their feed timestamps, divided by `speed`.
+    /// Pauses longer than 10 seconds are skipped.
+    pub fn paced(mut self, speed: f64) -> Self {
+        self.speed = Some(speed).filter(|speed| speed.is_finite() && *speed > 0.0);
+        self
+    }
+
+    /// When `line` is due, frames without a timestamp are due right away.
+    fn due(&mut self, line: &str) -> Instant {
+        let now = Instant::now();
+
+        let Some(speed) = self.speed else {
+            return now;
+        };
+
+        let Some(timestamp) = frame_timestamp(line) else {
+            return now;
+        };
+
+        let due = match self.clock {
+            Some((last, replayed)) => (timestamp - last)
+                .to_std()
+                .ok()
+                .map(|gap| gap.div_f64(speed))
+                .filter(|gap| *gap <= MAX_GAP)
+                .map(|gap| replayed + gap),
+            None => None,
+        };
+
+        // start over from now at the first frame, when going back in time or after a long pause
+        let due = due.unwrap_or(now);
+        self.clock = Some((timestamp, due));
+
+        due
+    }
+}
+
+/// Feed timestamp of the first message in a frame.
+fn frame_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    let frame = serde_json::from_str::<Frame>(line).ok()?;
+    let (_, _, timestamp) = &frame.m.first()?.a;
+    parse_timestamp(timestamp)
+}
+
+impl Transport for FileTransport {
+    async fn send(&mut self, text: String) -> Result<(), SignalrError> {
+        let invocation = serde_json::from_str::<Invocation>(&text)?;
+
+        debug!(method = invocation.m, "answering invocation from recording");
+
+        let response = match self.initial.take() {
+            Some(initial) if invocation.m == "Subscribe" => {
+                format!(r#"{{"R":{},"I":{}}}"#, initial, json!(invocation.i))
+            }
+            initial => {
+                self.initial = initial;
+                json!({ "I": invocation.i }).to_string()
+            }
+        };
+
+        self.responses.push_back(response);
+
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Option<Result<String, SignalrError>> {
+        if let Some(response) = self.responses.pop_front() {
+            return Some(Ok(response));
+        }
+
+        // like the server, don't send anything before we're subscribed
+        if self.initial.is_some() {
+            std::future::pending::<()>().await;
+        }
+
+        if self.next.is_none() {
+            let line = loop {
+                match self.lines.next_line().await {
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(line)) => break line,
+                    Ok(None) => return None,
+                    Err(err) => return Some(Err(err.into())),
+                }
+            };
+
+            let due = self.due(&line);
+            self.next = Some((line, due));
+        }
+
+        if let Some((_, due)) = &self.next {
+            sleep_until(*due).await;
+        }
+
+        self.next.take().map(|(line, _)| Ok(line))
+    }
+
+    async fn close(&mut self) {}
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::debug;
+
+use crate::{SignalrError, WsStream, transport::Transport};
+
+/// Transport over a WebSocket, as used by [`crate::create_client`].
+pub struct WebSocketTransport {
+    stream: WsStream,
+    read_timeout: Option<Duration>,
+}
+
+impl WebSocketTransport {
+    pub fn new(stream: WsStream) -> Self {
+        Self {
+            stream,
+            read_timeout: None,
+        }
+    }
+
+    /// Fail `recv` when no frame arrives within `timeout`.
+    pub fn read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+}
+
+impl Transport for WebSocketTransport {
+    async fn send(&mut self, text: String) -> Result<(), SignalrError> {
+        Ok(self.stream.send(Message::text(text)).await?)
+    }
+
+    async fn recv(&mut self) -> Option<Result<String, SignalrError>> {
+        loop {
+            let message = match self.read_timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, self.stream.next()).await {
+                    Ok(message) => message?,
+                    Err(_) => return Some(Err(SignalrError::Timeout("reading"))),
+                },
+                None => self.stream.next().await?,
+            };
+
+            match message {
+                Ok(Message::Text(txt)) => return Some(Ok(txt.to_string())),
+                Ok(Message::Close(frame)) => {
+                    debug!(?frame, "ws closed by server");
+                    return None;
+                }
+                Ok(_) => continue,
+                Err(err) => return Some(Err(err.into())),
+            }
+        }
+    }
+
+    async fn close(&mut self) {
+        let _ = self.stream.close(None).await;
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use futures::StreamExt;
+use serde_json::{Value, json};
+use signalr::{ChannelTransport, FileTransport, SignalrClient, SignalrError, Transport};
+use tokio::time::{Instant, timeout};
+
+/// Answers the first `Subscribe` with `state`, then sends `frames` in order.
+async fn serve(mut server: ChannelTransport, state: Value, frames: Vec<String>) {
+    let invoke = server.recv().await.expect("invocation").expect("text");
+    let invoke: Value = serde_json::from_str(&invoke).expect("json");
+
+    assert_eq!(invoke["H"], "Streaming");
+    assert_eq!(invoke["M"], "Subscribe");
+    assert_eq!(invoke["A"], json!([["Heartbeat", "TimingData"]]));
+
+    let response = json!({ "R": state, "I": invoke["I"] });
+    server.send(response.to_string()).await.expect("response");
+
+    for frame in frames {
+        server.send(frame).await.expect("frame");
+    }
+
+    server.close().await;
+}
+
+fn feed(topic: &str, data: Value, timestamp: &str) -> String {
+    json!({ "C": "d-1", "M": [{ "H": "Streaming", "M": "feed", "A": [topic, data, timestamp] }] })
+        .to_string()
+}
+
+async fn subscribed(frames: Vec<String>) -> (SignalrClient, Value) {
+    let (client, server) = ChannelTransport::pair();
+    tokio::spawn(serve(server, json!({ "Heartbeat": {} }), frames));
+
+    let mut client = SignalrClient::from_transport("Streaming", client);
+    let state = timeout(
+        Duration::from_secs(5),
+        signalr::subscribe(&mut client, &["Heartbeat", "TimingData"]),
+    )
+    .await
+    .expect("response in time")
+    .expect("subscribed");
+
+    (client, state)
+}
+
+#[tokio::test]
+async fn subscribe_returns_the_state() {
+    let (_client, state) = subscribed(Vec::new()).await;
+
+    assert_eq!(state, json!({ "Heartbeat": {} }));
+}
+
+#[tokio::test]
+async fn listen_parses_updates_and_skips_keep_alives() {
+    let frames = vec![
+        "{}".to_string(),
+        feed(
+            "TimingData",
+            json!({ "Lines": { "1": { "Position": "1" } } }),
+            "2024-05-01T12:00:01.1234567Z",
+        ),
+        "{}".to_string(),
+        feed("Heartbeat", json!({}), "2024-05-01T12:00:02Z"),
+    ];
+
+    let (client, _) = subscribed(frames).await;
+
+    let updates = signalr::listen(client)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    assert_eq!(updates.len(), 2);
+    assert_eq!(updates[0].topic, "TimingData");
+    assert_eq!(updates[0].data["Lines"]["1"]["Position"], "1");
+    assert_eq!(
+        updates[0].timestamp.to_rfc3339(),
+        "2024-05-01T12:00:01.123456700+00:00"
+    );
+    assert_eq!(updates[1].topic, "Heartbeat");
+}
+
+#[tokio::test]
+async fn listen_raw_passes_frames_on_unchanged() {
+    let frame = feed("Heartbeat", json!({}), "2024-05-01T12:00:02.1234567Z");
+    let unknown = json!({ "P": { "I": "3", "D": 1 } }).to_string();
+
+    let (client, _) = subscribed(vec![frame.clone(), "{}".to_string(), unknown.clone()]).await;
+
+    let frames = signalr::listen_raw(client).collect::<Vec<_>>().await;
+
+    assert_eq!(frames, [frame, unknown]);
+}
+
+#[tokio::test]
+async fn responses_with_other_ids_dont_resolve_an_invocation() {
+    let (client, mut server) = ChannelTransport::pair();
+    let client = SignalrClient::from_transport("Streaming", client);
+
+    tokio::spawn(async move {
+        let invoke = server.recv().await.expect("invocation").expect("text");
+        let invoke: Value = serde_json::from_str(&invoke).expect("json");
+
+        for response in [
+            json!({ "I": "unknown", "R": "wrong" }),
+            json!({ "I": invoke["I"], "R": "right" }),
+        ] {
+            server.send(response.to_string()).await.expect("response");
+        }
+
+        // keep the connection open until the client is done
+        server.recv().await;
+    });
+
+    let result = timeout(Duration::from_secs(5), client.invoke("Ping", Vec::new()))
+        .await
+        .expect("response in time")
+        .expect("ping");
+
+    assert_eq!(result, "right");
+}
+
+/// Sends `count` frames as fast as they're read, counting the reads.
+struct Flood {
+    sent: Arc<AtomicUsize>,
+    count: usize,
+}
+
+impl Transport for Flood {
+    async fn send(&mut self, _text: String) -> Result<(), SignalrError> {
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Option<Result<String, SignalrError>> {
+        let sent = self.sent.fetch_add(1, Ordering::SeqCst);
+        (sent < self.count).then(|| {
+            Ok(feed(
+                "Heartbeat",
+                json!({ "n": sent }),
+                "2024-05-01T12:00:00Z",
+            ))
+        })
+    }
+
+    async fn close(&mut self) {}
+}
+
+#[tokio::test]
+async fn unread_frames_hold_up_the_connection() {
+    let sent = Arc::new(AtomicUsize::new(0));
+    let transport = Flood {
+        sent: sent.clone(),
+        count: 5000,
+    };
+
+    let client = SignalrClient::from_transport("Streaming", transport);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // the connection stops reading once the queue is full
+    assert!(sent.load(Ordering::SeqCst) < 2000, "{sent:?}");
+
+    let updates = timeout(
+        Duration::from_secs(5),
+        signalr::listen(client).collect::<Vec<_>>(),
+    )
+    .await
+    .expect("all frames read");
+
+    let numbers = updates
+        .into_iter()
+        .flatten()
+        .map(|update| update.data["n"].as_u64().expect("number") as usize)
+        .collect::<Vec<_>>();
+
+    assert_eq!(numbers, (0..5000).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn file_transport_replays_a_recording() {
+    let path = recording(
+        "replays",
+        &[
+            r#"{"Heartbeat":{"Utc":"2024-05-01T12:00:00Z"}}"#.to_string(),
+            feed(
+                "Heartbeat",
+                json!({ "Utc": "2024-05-01T12:00:01Z" }),
+                "2024-05-01T12:00:01Z",
+            ),
+            String::new(),
+            r#"{"R":{"Heartbeat":{}}}"#.to_string(),
+            feed(
+                "Heartbeat",
+                json!({ "Utc": "2024-05-01T12:00:02Z" }),
+                "2024-05-01T12:00:02Z",
+            ),
+        ],
+    );
+
+    let transport = FileTransport::open(&path).await.expect("recording");
+    let mut client = SignalrClient::from_transport("Streaming", transport);
+
+    // other invocations resolve without a result before the subscription
+    let result = client.invoke("Ping", Vec::new()).await.expect("ping");
+    assert_eq!(result, Value::Null);
+
+    let state = signalr::subscribe(&mut client, &["Heartbeat"])
+        .await
+        .expect("subscribed");
+    assert_eq!(
+        state,
+        json!({ "Heartbeat": { "Utc": "2024-05-01T12:00:00Z" } })
+    );
+
+    let frames = timeout(
+        Duration::from_secs(5),
+        signalr::listen_raw(client).collect::<Vec<_>>(),
+    )
+    .await
+    .expect("recording ends");
+
+    // blank lines are skipped, everything else is received as it was recorded
+    assert_eq!(frames.len(), 3, "{frames:?}");
+    assert_eq!(frames[1], r#"{"R":{"Heartbeat":{}}}"#);
+
+    let _ = fs::remove_file(&path);
+}
+
+#[tokio::test(start_paused = true)]
+async fn file_transport_keeps_the_recorded_pace() {
+    let mut lines = vec!["{}".to_string()];
+    for second in [1, 3, 4] {
+        lines.push(feed(
+            "Heartbeat",
+            json!({}),
+            &format!("2024-05-01T12:00:0{second}Z"),
+        ));
+    }
+
+    let path = recording("paced", &lines);
+
+    let transport = FileTransport::open(&path)
+        .await
+        .expect("recording")
+        .paced(2.0);
+    let mut client = SignalrClient::from_transport("Streaming", transport);
+    signalr::subscribe(&mut client, &[])
+        .await
+        .expect("subscribed");
+
+    let start = Instant::now();
+    let stream = signalr::listen(client);
+    tokio::pin!(stream);
+
+    let mut received = Vec::new();
+    while stream.next().await.is_some() {
+        received.push(start.elapsed());
+    }
+
+    // two and one seconds of feed time at twice the speed
+    assert_eq!(
+        received,
+        [
+            Duration::ZERO,
+            Duration::from_secs(1),
+            Duration::from_millis(1500)
+        ]
+    );
+
+    let _ = fs::remove_file(&path);
+}
+
+/// A recording in the temp directory, `name` keeps tests running in parallel apart.
+fn recording(name: &str, lines: &[String]) -> PathBuf {
+    let path = env::temp_dir().join(format!("signalr-{name}-{}.txt", process::id()));
+    fs::write(&path, lines.join("\n")).expect("recording written");
+
+    path
+}
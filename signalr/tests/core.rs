@@ -0,0 +1,143 @@
+use serde_json::{Value, json};
+use signalr::{ChannelTransport, CoreTransport, SignalrError, Transport};
+
+const RS: char = '\u{1e}';
+
+/// A core transport past its handshake, and the server end of the connection.
+async fn connect() -> (CoreTransport<ChannelTransport>, ChannelTransport) {
+    let (client, mut server) = ChannelTransport::pair();
+
+    server
+        .send(format!("{{}}{RS}"))
+        .await
+        .expect("handshake response");
+
+    let transport = CoreTransport::handshake(client, "Streaming")
+        .await
+        .expect("handshake");
+
+    let handshake = server.recv().await.expect("handshake").expect("text");
+    assert_eq!(
+        handshake,
+        format!(r#"{{"protocol":"json","version":1}}{RS}"#)
+    );
+
+    (transport, server)
+}
+
+async fn translate(records: &[Value]) -> Vec<Value> {
+    let (mut transport, mut server) = connect().await;
+
+    let txt = records
+        .iter()
+        .map(|record| format!("{record}{RS}"))
+        .collect::<String>();
+    server.send(txt).await.expect("send");
+    server.close().await;
+
+    let mut frames = Vec::new();
+    while let Some(frame) = transport.recv().await {
+        frames.push(serde_json::from_str(&frame.expect("frame")).expect("json"));
+    }
+
+    frames
+}
+
+#[tokio::test]
+async fn invocations_become_feed_frames() {
+    let frames = translate(&[json!({
+        "type": 1,
+        "target": "feed",
+        "arguments": ["Heartbeat", {}, "2024-05-01T12:00:00.000Z"]
+    })])
+    .await;
+
+    assert_eq!(
+        frames,
+        [
+            json!({ "M": [{ "H": "Streaming", "M": "feed", "A": ["Heartbeat", {}, "2024-05-01T12:00:00.000Z"] }] })
+        ]
+    );
+}
+
+#[tokio::test]
+async fn stream_items_become_progress_frames() {
+    let frames = translate(&[
+        json!({ "type": 2, "invocationId": "1", "item": { "Lap": 12 } }),
+        json!({ "type": 2, "item": { "Lap": 13 } }),
+    ])
+    .await;
+
+    assert_eq!(frames, [json!({ "P": { "I": "1", "D": { "Lap": 12 } } })]);
+}
+
+#[tokio::test]
+async fn completions_become_responses() {
+    let frames = translate(&[
+        json!({ "type": 3, "invocationId": "0", "result": { "Heartbeat": {} } }),
+        json!({ "type": 3, "invocationId": "1", "error": "unknown topic" }),
+        json!({ "type": 3, "result": {} }),
+    ])
+    .await;
+
+    assert_eq!(
+        frames,
+        [
+            json!({ "I": "0", "R": { "Heartbeat": {} } }),
+            json!({ "I": "1", "E": "unknown topic" }),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn pings_become_keep_alives_and_unknown_kinds_are_skipped() {
+    let frames = translate(&[
+        json!({ "type": 6 }),
+        json!({ "type": 4, "invocationId": "2" }),
+    ])
+    .await;
+
+    assert_eq!(frames, [json!({})]);
+}
+
+#[tokio::test]
+async fn close_messages_end_the_stream() {
+    let (mut transport, mut server) = connect().await;
+
+    server
+        .send(format!(
+            r#"{{"type":7,"error":"shutting down","allowReconnect":true}}{RS}"#
+        ))
+        .await
+        .expect("send");
+    assert!(transport.recv().await.is_none());
+
+    let (mut transport, mut server) = connect().await;
+
+    server
+        .send(format!(r#"{{"type":7,"error":"unauthorized"}}{RS}"#))
+        .await
+        .expect("send");
+    assert!(matches!(
+        transport.recv().await,
+        Some(Err(SignalrError::Protocol(err))) if err.contains("unauthorized")
+    ));
+}
+
+#[tokio::test]
+async fn invocations_are_sent_as_core_messages() {
+    let (mut transport, mut server) = connect().await;
+
+    transport
+        .send(r#"{"H":"Streaming","M":"Subscribe","A":[["Heartbeat"]],"I":"0"}"#.to_string())
+        .await
+        .expect("send");
+
+    let txt = server.recv().await.expect("message").expect("text");
+    let message: Value = serde_json::from_str(txt.trim_end_matches(RS)).expect("json");
+
+    assert_eq!(
+        message,
+        json!({ "type": 1, "invocationId": "0", "target": "Subscribe", "arguments": [["Heartbeat"]] })
+    );
+}
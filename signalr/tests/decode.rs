@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde_json::{Value, json};
+use signalr::{ChannelTransport, SignalrClient, SignalrError, Transport};
+use tokio::time::timeout;
+
+/// `CarData` entries as raw deflate, base64 encoded like the feed sends `CarData.z`.
+const CAR_DATA: &str = "HYqxCoNAEET/ZepTZtc7lW3FPzBNJIUEwUC4wtgd/rtrhmEePKZgzMf+WX+wueBxvGFQaqyYKsokaqS3Fm1iarsnAoZl93eB3DNsS87r9y8IEyEZoDDtnQ2sC4jub5tgvtHRn57XeQE=";
+
+/// Valid base64, but not deflate.
+const CORRUPT: &str = "bm90IGRlZmxhdGU=";
+
+fn car_data() -> Value {
+    json!({
+        "Entries": [{
+            "Utc": "2024-05-01T12:00:00.1234567Z",
+            "Cars": { "1": { "Channels": { "0": 11000, "2": 280, "3": 7, "4": 100, "5": 0, "45": 8 } } }
+        }]
+    })
+}
+
+#[test]
+fn inflates_compressed_payloads() {
+    assert_eq!(signalr::inflate(CAR_DATA).expect("inflated"), car_data());
+
+    assert!(matches!(
+        signalr::inflate(CORRUPT),
+        Err(SignalrError::Decode(_))
+    ));
+    assert!(matches!(
+        signalr::inflate("not base64!"),
+        Err(SignalrError::Decode(_))
+    ));
+}
+
+#[test]
+fn decodes_compressed_topics_of_a_state() {
+    let state = json!({
+        "CarData.z": CAR_DATA,
+        "Position.z": CORRUPT,
+        "Heartbeat": { "Utc": "2024-05-01T12:00:00Z" }
+    });
+
+    assert_eq!(
+        signalr::decode_state(state),
+        json!({
+            "CarData": car_data(),
+            // left as it is when it doesn't decode
+            "Position.z": CORRUPT,
+            "Heartbeat": { "Utc": "2024-05-01T12:00:00Z" }
+        })
+    );
+}
+
+#[tokio::test]
+async fn listen_decoded_inflates_updates_and_passes_corrupt_ones_through() {
+    let (client, mut server) = ChannelTransport::pair();
+
+    let frame = json!({ "M": [
+        { "H": "Streaming", "M": "feed", "A": ["CarData.z", CAR_DATA, "2024-05-01T12:00:00.1234567Z"] },
+        { "H": "Streaming", "M": "feed", "A": ["Position.z", CORRUPT, "2024-05-01T12:00:00.1234567Z"] },
+        { "H": "Streaming", "M": "feed", "A": ["Heartbeat", {}, "2024-05-01T12:00:00.1234567Z"] }
+    ] });
+
+    server.send(frame.to_string()).await.expect("frame");
+    server.close().await;
+
+    let client = SignalrClient::from_transport("Streaming", client);
+    let updates = timeout(
+        Duration::from_secs(5),
+        signalr::listen_decoded(client).collect::<Vec<_>>(),
+    )
+    .await
+    .expect("stream ends");
+
+    let updates = updates
+        .into_iter()
+        .flatten()
+        .map(|update| (update.topic, update.data))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        updates,
+        [
+            ("CarData".to_string(), car_data()),
+            ("Position.z".to_string(), json!(CORRUPT)),
+            ("Heartbeat".to_string(), json!({})),
+        ]
+    );
+}
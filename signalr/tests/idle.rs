@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use serde_json::json;
+use signalr::{ChannelTransport, ClientBuilder, SignalrError, Transport};
+use tokio::time::{Instant, sleep};
+
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sends an update now and after 20s, and a keep-alive every 10s until the client is gone.
+async fn serve(mut server: ChannelTransport) {
+    let update = json!({ "M": [{ "H": "Streaming", "M": "feed", "A": ["Heartbeat", {}, "2024-05-01T12:00:00.000Z"] }] })
+        .to_string();
+
+    for second in (0..).step_by(10) {
+        let frame = match second {
+            0 | 20 => update.clone(),
+            _ => "{}".to_string(),
+        };
+
+        if server.send(frame).await.is_err() {
+            return;
+        }
+
+        sleep(Duration::from_secs(10)).await;
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn keep_alives_dont_reset_the_idle_timeout() {
+    let (client, server) = ChannelTransport::pair();
+    tokio::spawn(serve(server));
+
+    let client = ClientBuilder::new("localhost/signalr", "Streaming")
+        .idle_timeout(IDLE_TIMEOUT)
+        .from_transport(client);
+
+    let start = Instant::now();
+    let close_reason = client.close_reason();
+    let stream = signalr::listen_raw(client);
+    tokio::pin!(stream);
+
+    let mut received = Vec::new();
+    while stream.next().await.is_some() {
+        received.push(start.elapsed().as_secs());
+    }
+
+    // keep-alives at 10s, 30s and 40s never reach the stream nor hold the connection open
+    assert_eq!(received, [0, 20]);
+    assert_eq!(start.elapsed().as_secs(), 50);
+    assert!(
+        matches!(close_reason.take(), Some(SignalrError::Idle(timeout)) if timeout == IDLE_TIMEOUT)
+    );
+}
@@ -0,0 +1,43 @@
+use signalr::{ClientBuilder, SignalrError};
+use tokio::{io::AsyncReadExt, net::TcpListener};
+
+fn client(proxy: &str) -> ClientBuilder {
+    ClientBuilder::new("localhost/signalr", "Streaming")
+        .endpoint(Some("ws://localhost:1/ws".to_string()))
+        .proxy(proxy)
+}
+
+#[tokio::test]
+async fn unreachable_proxy_is_retryable() {
+    // bound and dropped, so nothing listens on the port anymore
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener");
+    let address = listener.local_addr().expect("address");
+    drop(listener);
+
+    let Err(err) = client(&format!("http://{address}")).connect().await else {
+        panic!("connected without a proxy");
+    };
+
+    assert!(matches!(err, SignalrError::Proxy(_)), "{err:?}");
+    assert!(err.is_retryable());
+}
+
+#[tokio::test]
+async fn proxy_closing_the_connection_is_retryable() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener");
+    let address = listener.local_addr().expect("address");
+
+    // reads the CONNECT request, then hangs up without an answer
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.expect("connection");
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).await;
+    });
+
+    let Err(err) = client(&format!("http://{address}")).connect().await else {
+        panic!("connected through a closed tunnel");
+    };
+
+    assert!(matches!(err, SignalrError::Proxy(_)), "{err:?}");
+    assert!(err.is_retryable());
+}
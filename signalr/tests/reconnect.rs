@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use signalr::ReconnectPolicy;
+
+#[test]
+fn delay_grows_and_stays_within_max() {
+    let policy = ReconnectPolicy::default();
+
+    for attempt in 1..=10 {
+        let base = policy
+            .initial_delay
+            .mul_f64(policy.multiplier.powi(attempt as i32 - 1))
+            .min(policy.max_delay);
+
+        let delay = policy.delay(attempt);
+
+        // equal jitter, between half and the full base delay
+        assert!(
+            delay >= base / 2 - Duration::from_millis(1),
+            "attempt {attempt}: {delay:?}"
+        );
+        assert!(delay <= base, "attempt {attempt}: {delay:?}");
+    }
+}
+
+#[test]
+fn delay_doesnt_overflow_for_long_outages() {
+    let policy = ReconnectPolicy::default();
+
+    for attempt in [64, 65, 66, 100, 1_000, u32::MAX] {
+        let delay = policy.delay(attempt);
+
+        assert!(
+            delay >= policy.max_delay / 2,
+            "attempt {attempt}: {delay:?}"
+        );
+        assert!(delay <= policy.max_delay, "attempt {attempt}: {delay:?}");
+    }
+}
+
+#[test]
+fn restart_delay_doesnt_overflow() {
+    let policy = ReconnectPolicy {
+        initial_delay: Duration::from_secs(2),
+        max_delay: Duration::from_secs(60),
+        multiplier: 2.0,
+        max_attempts: None,
+    };
+
+    assert!(policy.delay(64) <= policy.max_delay);
+    assert!(policy.delay(u32::MAX) <= policy.max_delay);
+}
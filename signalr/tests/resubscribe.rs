@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use signalr::{ClientBuilder, Event, ReconnectPolicy};
+use tokio::{net::TcpListener, time::timeout};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+/// Answers every `Subscribe` with a state counting the subscriptions,
+/// and sends a feed message every 20ms.
+async fn serve(listener: TcpListener) {
+    let (stream, _) = listener.accept().await.expect("connection");
+    let mut socket = accept_async(stream).await.expect("websocket handshake");
+
+    let mut subscriptions = 0;
+    let mut ticker = tokio::time::interval(Duration::from_millis(20));
+
+    loop {
+        tokio::select! {
+            message = socket.next() => {
+                let Some(Ok(Message::Text(text))) = message else {
+                    return;
+                };
+
+                let invoke: Value = serde_json::from_str(&text).expect("invocation");
+                assert_eq!(invoke["M"], "Subscribe");
+
+                subscriptions += 1;
+                let response = json!({ "R": { "Subscriptions": subscriptions }, "I": invoke["I"] });
+
+                if socket.send(Message::text(response.to_string())).await.is_err() {
+                    return;
+                }
+            }
+            _ = ticker.tick() => {
+                let frame = json!({ "M": [{ "H": "Streaming", "M": "feed", "A": ["Heartbeat", {}, "2024-05-01T12:00:00.000Z"] }] });
+
+                if socket.send(Message::text(frame.to_string())).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn resubscribes_on_the_running_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener");
+    let endpoint = format!("ws://{}/ws", listener.local_addr().expect("address"));
+
+    tokio::spawn(serve(listener));
+
+    let client = ClientBuilder::new("localhost/signalr", "Streaming")
+        .endpoint(Some(endpoint))
+        .reconnecting(&["Heartbeat"], ReconnectPolicy::default());
+
+    let resubscriber = client.resubscriber();
+    let stream = client.listen();
+    tokio::pin!(stream);
+
+    let mut next = async || {
+        timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("event in time")
+            .expect("stream continues")
+    };
+
+    assert!(matches!(next().await, Event::Subscribed(state) if state["Subscriptions"] == 1));
+    assert!(matches!(next().await, Event::Message(_)));
+
+    resubscriber.resubscribe();
+
+    // messages keep flowing until the state arrives, on the same connection
+    loop {
+        match next().await {
+            Event::Message(_) => continue,
+            Event::Subscribed(state) => {
+                assert_eq!(state["Subscriptions"], 2);
+                break;
+            }
+            event => panic!("unexpected {event:?}"),
+        }
+    }
+
+    assert!(matches!(next().await, Event::Message(_)));
+}
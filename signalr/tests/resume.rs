@@ -0,0 +1,186 @@
+use std::{collections::HashMap, time::Duration};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::json;
+use signalr::ClientBuilder;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time::timeout,
+};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        Message,
+        handshake::server::{Request, Response},
+    },
+};
+
+/// What the server saw of a websocket request.
+struct Opened {
+    path: String,
+    query: HashMap<String, String>,
+    cookie: Option<String>,
+}
+
+/// Answers `/negotiate` over plain http, and on every websocket connection reports
+/// the request and sends `frames` before closing it.
+#[allow(clippy::result_large_err)] // the handshake callback's signature is given by tungstenite
+async fn serve(listener: TcpListener, frames: Vec<String>, opened: mpsc::UnboundedSender<Opened>) {
+    loop {
+        let (stream, _) = listener.accept().await.expect("connection");
+
+        let mut head = [0; 256];
+        let read = stream.peek(&mut head).await.expect("request");
+
+        if String::from_utf8_lossy(&head[..read]).starts_with("GET /signalr/negotiate") {
+            negotiate(stream).await;
+            continue;
+        }
+
+        let mut request = None;
+        let mut socket = accept_hdr_async(stream, |req: &Request, res: Response| {
+            request = Some(opened_from(req));
+            Ok(res)
+        })
+        .await
+        .expect("websocket handshake");
+
+        opened
+            .send(request.expect("request seen"))
+            .expect("test listening");
+
+        for frame in &frames {
+            socket
+                .send(Message::text(frame.clone()))
+                .await
+                .expect("frame");
+        }
+
+        let _ = socket.close(None).await;
+    }
+}
+
+async fn negotiate(mut stream: TcpStream) {
+    let mut request = Vec::new();
+    while !request.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).await.expect("request");
+        request.extend(byte);
+    }
+
+    let body = json!({ "ConnectionToken": "token/1+2" }).to_string();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nSet-Cookie: affinity=abc\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    stream
+        .write_all(response.as_bytes())
+        .await
+        .expect("response");
+}
+
+fn opened_from(req: &Request) -> Opened {
+    let url = reqwest::Url::parse(&format!("http://localhost{}", req.uri())).expect("uri");
+    let query = url.query_pairs().into_owned().collect();
+
+    Opened {
+        path: req.uri().path().to_string(),
+        query,
+        cookie: req
+            .headers()
+            .get("cookie")
+            .and_then(|cookie| cookie.to_str().ok())
+            .map(str::to_string),
+    }
+}
+
+/// A feed frame with the `C` and `G` fields in `position`.
+fn feed(position: &[(&str, &str)]) -> String {
+    let mut frame = json!({ "M": [{ "H": "Streaming", "M": "feed", "A": ["Heartbeat", {}, "2024-05-01T12:00:00.000Z"] }] });
+
+    for (key, value) in position {
+        frame[key] = json!(value);
+    }
+
+    frame.to_string()
+}
+
+#[tokio::test]
+async fn resumes_from_the_tracked_cursor() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener");
+    let url = format!("{}/signalr", listener.local_addr().expect("address"));
+
+    let frames = vec![
+        feed(&[("C", "d-1,1"), ("G", "groups-1")]),
+        feed(&[]),
+        // a later message id keeps the groups token it came without
+        feed(&[("C", "d-1,2")]),
+    ];
+
+    let (opened_tx, mut opened) = mpsc::unbounded_channel();
+    tokio::spawn(serve(listener, frames, opened_tx));
+
+    let client = ClientBuilder::new(&url, "Streaming")
+        .secure(false)
+        .connect()
+        .await
+        .expect("connected");
+
+    let connect = opened.recv().await.expect("connect");
+    assert_eq!(connect.path, "/signalr/connect");
+    assert_eq!(connect.query["connectionToken"], "token/1+2");
+
+    let handle = client.resume_handle().expect("resumable");
+
+    let received = timeout(
+        Duration::from_secs(5),
+        signalr::listen_raw(client).collect::<Vec<_>>(),
+    )
+    .await
+    .expect("connection closes");
+    assert_eq!(received.len(), 3);
+
+    let cursor = handle.cursor();
+    assert_eq!(cursor.message_id.as_deref(), Some("d-1,2"));
+    assert_eq!(cursor.groups_token.as_deref(), Some("groups-1"));
+
+    signalr::reconnect(&handle).await.expect("resumed");
+
+    let reconnect = opened.recv().await.expect("reconnect");
+    assert_eq!(reconnect.path, "/signalr/reconnect");
+    assert_eq!(reconnect.query["transport"], "webSockets");
+    assert_eq!(reconnect.query["connectionToken"], "token/1+2");
+    assert_eq!(reconnect.query["messageId"], "d-1,2");
+    assert_eq!(reconnect.query["groupsToken"], "groups-1");
+    assert_eq!(
+        reconnect.query["connectionData"],
+        json!([{ "name": "Streaming" }]).to_string()
+    );
+    assert_eq!(reconnect.cookie.as_deref(), Some("affinity=abc"));
+}
+
+#[tokio::test]
+async fn needs_a_message_id_to_resume() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener");
+    let url = format!("{}/signalr", listener.local_addr().expect("address"));
+
+    let (opened_tx, _opened) = mpsc::unbounded_channel();
+    tokio::spawn(serve(listener, vec![feed(&[])], opened_tx));
+
+    let client = ClientBuilder::new(&url, "Streaming")
+        .secure(false)
+        .connect()
+        .await
+        .expect("connected");
+
+    let handle = client.resume_handle().expect("resumable");
+    signalr::listen_raw(client).collect::<Vec<_>>().await;
+
+    assert!(matches!(
+        signalr::reconnect(&handle).await,
+        Err(signalr::SignalrError::Protocol(_))
+    ));
+}
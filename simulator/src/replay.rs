@@ -20,9 +20,10 @@ pub async fn replay(path: &Path) -> Result<(), Error> {
 
     let buffer = BufReader::new(file);
 
+    // a failing reader yields the same error for every further line, stop at it
     let lines = buffer
         .lines()
-        .filter_map(|line| line.ok())
+        .map_while(Result::ok)
         .collect::<Vec<String>>();
 
     server::run(lines).await
@@ -2,36 +2,44 @@ use std::{
     fs::File,
     io::{LineWriter, Write},
     path::Path,
+    time::Duration,
 };
 
-use anyhow::Error;
+use anyhow::{Context, Error};
+use serde::Deserialize;
+use serde_json::json;
+use shared::config::{UpstreamConfig, env_override, load_config};
+use signalr::{ClientBuilder, Event, ReconnectPolicy};
 use tokio_stream::StreamExt;
-use tracing::{debug, info};
-
-const URL: &str = "livetiming.formula1.com/signalr";
-const HUB: &str = "Streaming";
-
-const TOPICS: [&str; 17] = [
-    "Heartbeat",
-    "CarData.z",
-    "Position.z",
-    "ExtrapolatedClock",
-    "TimingStats",
-    "TimingAppData",
-    "WeatherData",
-    "TrackStatus",
-    "SessionStatus",
-    "DriverList",
-    "RaceControlMessages",
-    "SessionInfo",
-    "SessionData",
-    "LapCount",
-    "TimingData",
-    "TeamRadio",
-    "ChampionshipPrediction",
-];
+use tracing::{debug, info, warn};
+
+/// The `[upstream]` table of a realtime config, other tables are ignored.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Config {
+    upstream: UpstreamConfig,
+    /// Seconds between full state snapshots from the server, none when 0, `SNAPSHOT_INTERVAL`.
+    #[serde(skip)]
+    snapshot_interval: u64,
+}
+
+impl Config {
+    fn load() -> Result<Self, Error> {
+        let mut config: Config = load_config()?;
+
+        config.upstream.env_overrides()?;
+        env_override("SNAPSHOT_INTERVAL", &mut config.snapshot_interval)?;
+
+        config.upstream.validate().context("invalid config")?;
+
+        Ok(config)
+    }
+}
 
 pub async fn save(path: &Path) -> Result<(), Error> {
+    let config = Config::load()?;
+    let upstream = config.upstream;
+
     if path.exists() {
         return Err(anyhow::anyhow!(
             "File already exists at path {}",
@@ -54,29 +62,67 @@ pub async fn save(path: &Path) -> Result<(), Error> {
 
     info!("Connecting to F1 SignalR...");
 
-    let mut signalr_client = signalr::create_client(URL, HUB).await?;
+    let mut builder = ClientBuilder::new(upstream.url(), &upstream.hub)
+        .protocol(upstream.protocol.parse()?)
+        .endpoint(upstream.dev_url.clone());
 
-    info!("Subscribing to topics...");
+    if let Some(timeout) = upstream.idle_timeout() {
+        builder = builder.idle_timeout(timeout);
+    }
 
-    let initial = signalr::subscribe(&mut signalr_client, &TOPICS).await?;
+    let mut client = builder.reconnecting(&upstream.topics(), ReconnectPolicy::default());
 
-    // Save the initial state as the first line (as JSON)
-    let initial_json = serde_json::to_string(&initial)?;
-    writeln!(writer, "{}", initial_json)?;
-    debug!("Saved initial state");
+    // periodic full states from the server, to check the merge against
+    if config.snapshot_interval > 0 {
+        info!(secs = config.snapshot_interval, "Recording state snapshots");
+        client = client.snapshot_interval(Duration::from_secs(config.snapshot_interval));
+    }
 
-    info!("Listening for updates... Press Ctrl+C to stop.");
+    let stream = client.listen_raw();
+    tokio::pin!(stream);
 
-    let mut stream = signalr::listen_raw(signalr_client);
     let mut count: u64 = 0;
-
-    while let Some(raw_message) = stream.next().await {
-        // Write the raw message as-is (it's already JSON from the WebSocket)
-        writeln!(writer, "{}", raw_message)?;
-        count += 1;
-
-        if count % 100 == 0 {
-            debug!(count, "Saved messages");
+    let mut subscribed = false;
+
+    while let Some(event) = stream.next().await {
+        match event {
+            Event::Subscribed(initial) if !subscribed => {
+                // Save the initial state as the first line (as JSON)
+                let initial_json = serde_json::to_string(&initial)?;
+                writeln!(writer, "{}", initial_json)?;
+                debug!("Saved initial state");
+
+                subscribed = true;
+                info!("Listening for updates... Press Ctrl+C to stop.");
+            }
+            Event::Subscribed(initial) => {
+                // Re-subscribed after a reconnect, save the fresh state the way
+                // the server would have sent it so the recording can resync
+                let response_json = serde_json::to_string(&json!({ "R": initial }))?;
+                writeln!(writer, "{}", response_json)?;
+                info!("Resubscribed after reconnect");
+            }
+            Event::Resumed => info!("Resumed connection"),
+            Event::Reconnecting {
+                attempt,
+                delay,
+                reason,
+            } => {
+                warn!(attempt, ?delay, %reason, "Lost connection, reconnecting...");
+            }
+            Event::Failed(err) => {
+                writer.flush()?;
+                return Err(err.into());
+            }
+            Event::Message(raw_message) => {
+                // Write the raw message as-is (it's already JSON from the WebSocket)
+                writeln!(writer, "{}", raw_message)?;
+                count += 1;
+
+                if count.is_multiple_of(100) {
+                    debug!(count, "Saved messages");
+                }
+            }
         }
     }
 